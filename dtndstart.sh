@@ -1,3 +1,20 @@
 #!/bin/bash
 
-dtnd -n $(hostname) -e incoming -C mtcp -p 3s -r epidemic
+# starts dtnd the way the client's managed dtnd is started, registering the inbox and channel endpoints.
+# usage: dtndstart.sh [endpoint [channel...]]
+# without arguments they are read from config.json with jq, or the inbox is incoming if that is not possible
+if [ $# -gt 0 ]; then
+    endpoints=("$@")
+elif [ -f config.json ] && command -v jq > /dev/null; then
+    mapfile -t endpoints < <(jq -r '(.endpoint // "incoming"), (.channels // [])[]' config.json)
+else
+    endpoints=(incoming)
+fi
+
+args=()
+for endpoint in "${endpoints[@]}"; do
+    args+=(-e "$endpoint")
+done
+
+# delivery tracking needs the status reports the bundles ask for
+dtnd -n "$(hostname)" "${args[@]}" -C mtcp -p 3s -r epidemic -D sled --generate-status-reports
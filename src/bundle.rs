@@ -0,0 +1,63 @@
+use std::time::Duration;
+use bp7::flags::{BlockControlFlags, BundleControlFlags};
+use bp7::bundle::BundleBuilder;
+use bp7::{canonical, crc, primary, Bundle, CreationTimestamp};
+use crate::eid::Eid;
+use crate::options::{Priority, SendOptions};
+
+//block type of the priority block, from the range rfc 9171 leaves for private use
+pub const PRIORITY_BLOCK: u64 = 192;
+
+//builds the bundle carrying payload from source to destination the way options ask for,
+//status reports about it are sent to report_to
+pub fn build(source: &Eid, destination: &Eid, report_to: &Eid, payload: Vec<u8>, options: &SendOptions) -> Bundle {
+    //forwarding and deletion are always reported, so the chat log can tell how far a message got
+    let mut flags = BundleControlFlags::BUNDLE_STATUS_REQUEST_FORWARD
+        | BundleControlFlags::BUNDLE_STATUS_REQUEST_DELETION;
+    if options.report_delivery {
+        flags |= BundleControlFlags::BUNDLE_STATUS_REQUEST_DELIVERY;
+    }
+    if options.no_fragment {
+        flags |= BundleControlFlags::BUNDLE_MUST_NOT_FRAGMENTED;
+    }
+    let primary = primary::PrimaryBlockBuilder::default()
+        .bundle_control_flags(flags.bits())
+        .destination(destination.clone().into())
+        .source(source.clone().into())
+        .report_to(report_to.clone().into())
+        .creation_timestamp(CreationTimestamp::now())
+        .lifetime(Duration::from_secs(options.lifetime))
+        .build()
+        .expect("primary block has all fields set");
+    //the builder puts the payload block last, where bpv7 wants it
+    let mut bundle = BundleBuilder::default()
+        .primary(primary)
+        .canonicals(vec![
+            canonical::new_payload_block(BlockControlFlags::empty(), payload),
+            canonical::new_hop_count_block(2, BlockControlFlags::empty(), 32),
+            priority_block(3, options.priority),
+        ])
+        .build()
+        .expect("bundle has a primary block");
+    //receivers check the crc of every block, so corruption on the way shows
+    bundle.set_crc(crc::CRC_32);
+    bundle
+}
+
+//nodes that dont know the priority block keep it and forward it, since neither the flag to delete the bundle
+//nor the one to discard the block when it cant be processed is set. it is replicated in every fragment,
+//so the fragments of a bundle keep its priority
+fn priority_block(block_number: u64, priority: Priority) -> canonical::CanonicalBlock {
+    //the block data is the class as a cbor number, numbers below 24 are encoded as themselves
+    let class: u8 = match priority {
+        Priority::Bulk => 0,
+        Priority::Normal => 1,
+        Priority::Expedited => 2,
+    };
+    canonical::new_canonical_block(
+        PRIORITY_BLOCK,
+        block_number,
+        BlockControlFlags::BLOCK_REPLICATE.bits(),
+        canonical::CanonicalData::Unknown(vec![class]),
+    )
+}
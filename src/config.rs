@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::options::SendOptions;
+
+const CONFIG_FILE: &str = "config.json";
+
+//directory config.json, chat.json and the other files of the client are kept in,
+//the working directory unless set_data_dir() named another
+static DATA_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+//keeps the files of the client in dir from now on, creating it if needed
+pub fn set_data_dir(dir: PathBuf) -> io::Result<()> {
+    fs::create_dir_all(&dir)?;
+    *DATA_DIR.lock().unwrap() = Some(dir);
+    Ok(())
+}
+
+//path of the client's file named name in the data directory
+pub fn data_file(name: &str) -> PathBuf {
+    match DATA_DIR.lock().unwrap().as_ref() {
+        Some(dir) => dir.join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    //service name of our inbox, peers are sent to the same service on their node
+    pub endpoint: String,
+    //name others see us as, the node name when empty
+    pub name: String,
+    //more service names to listen on, each one is a shared channel in the peer list
+    pub channels: Vec<String>,
+    //chat rooms we are in, joined with /join and left with /leave
+    pub rooms: Vec<String>,
+    //lifetime, priority and flags of messages, unless a conversation has its own
+    pub send: SendOptions,
+    //options set with /set, by the name of the conversation in the peer list
+    pub conversations: BTreeMap<String, SendOptions>,
+    pub dtnd: DaemonConfig,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            endpoint: String::from("incoming"),
+            name: String::new(),
+            channels: Vec::new(),
+            rooms: Vec::new(),
+            send: SendOptions::default(),
+            conversations: BTreeMap::new(),
+            dtnd: DaemonConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    //options messages to the named conversation are sent with
+    pub fn options_for(&self, conversation: &str) -> SendOptions {
+        self.conversations.get(conversation).unwrap_or(&self.send).clone()
+    }
+}
+
+//where the client finds its dtnd and how it gets there,
+//node_name, cla, discovery_period and routing are the arguments dtndstart.sh passes
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    //address and web port of the dtnd to talk to
+    pub host: String,
+    pub port: u16,
+    //true starts a dtnd of our own and stops it on exit,
+    //false attaches to a dtnd that is already running and leaves it alone
+    pub managed: bool,
+    //node name of the started dtnd, the hostname when empty
+    pub node_name: String,
+    pub cla: String,
+    pub discovery_period: String,
+    pub routing: String,
+    //bundle store of the managed dtnd, sled keeps bundles on disk across the restarts /peer needs,
+    //mem loses them
+    pub db: String,
+    //peers discovery cant find, added with /peer add and passed to the managed dtnd
+    pub static_peers: Vec<StaticPeer>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> DaemonConfig {
+        DaemonConfig {
+            host: String::from("127.0.0.1"),
+            port: 3000,
+            managed: false,
+            node_name: String::new(),
+            cla: String::from("mtcp"),
+            discovery_period: String::from("3s"),
+            routing: String::from("epidemic"),
+            db: String::from("sled"),
+            static_peers: Vec::new(),
+        }
+    }
+}
+
+//convergence layers dtnd can reach a single peer at a known address with
+const STATIC_CLAS: [&str; 3] = ["mtcp", "tcp", "http"];
+
+//a peer dtnd is told about instead of finding it by discovery
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StaticPeer {
+    pub cla: String,
+    pub address: String,
+    pub port: u16,
+    //node name of the peer
+    pub node: String,
+}
+
+impl StaticPeer {
+    //checks everything dtnd would otherwise stumble over when starting, the error says what is wrong
+    pub fn new(cla: &str, address: &str, port: &str, node: &str) -> Result<StaticPeer, String> {
+        if !STATIC_CLAS.contains(&cla) {
+            return Err(format!("Unknown CLA {}, use one of {}", cla, STATIC_CLAS.join(", ")));
+        }
+        if address.is_empty() || address.contains('/') {
+            return Err(format!("Invalid address {}", address));
+        }
+        let port = port.parse().map_err(|_| format!("Invalid port {}", port))?;
+        let valid_node = node.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+            && node.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+        if !valid_node {
+            return Err(format!("Invalid node name {}", node));
+        }
+        Ok(StaticPeer { cla: cla.to_string(), address: address.to_string(), port, node: node.to_string() })
+    }
+
+    //the peer the way dtnd -s takes it, like mtcp://192.168.2.1:16162/node2
+    pub fn url(&self) -> String {
+        format!("{}://{}:{}/{}", self.cla, self.address, self.port, self.node)
+    }
+}
+
+//reads config.json, creating it with the defaults first if it doesnt exist
+pub fn load() -> io::Result<Config> {
+    if !data_file(CONFIG_FILE).exists() {
+        save(&Config::default())?;
+    }
+    read_file(CONFIG_FILE)
+}
+
+//writes config to config.json, replacing what was there
+pub fn save(config: &Config) -> io::Result<()> {
+    write_file(CONFIG_FILE, config)
+}
+
+//reads the json file named name in the data directory, a missing one holds the default.
+//one that doesn't parse is an error, so what it holds is not written over with less
+pub fn read_file<T: DeserializeOwned + Default>(name: &str) -> io::Result<T> {
+    let path = data_file(name);
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    serde_json::from_str(&contents)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", name, err)))
+}
+
+//writes value as json to the file named name in the data directory. it goes to a new file first
+//which then takes the old one's place, so a write cut short leaves the old file as it was
+pub fn write_file<T: Serialize + ?Sized>(name: &str, value: &T) -> io::Result<()> {
+    let contents = serde_json::to_string_pretty(value)?;
+    let path = data_file(name);
+    let temporary = path.with_extension("json.tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(temporary, path)
+}
+
+//unix time in seconds
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+//the data directory is shared by the whole process, so tests using it take turns
+#[cfg(test)]
+static TEST_DATA_DIR: Mutex<()> = Mutex::new(());
+
+//an empty data directory with an empty chat log for the test named name, in the system's temporary directory.
+//it is the test's until the guard is dropped
+#[cfg(test)]
+pub fn test_data_dir(name: &str) -> (std::sync::MutexGuard<'static, ()>, PathBuf) {
+    let guard = TEST_DATA_DIR.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let dir = std::env::temp_dir().join(format!("dtnclient-{}-{}", name, std::process::id()));
+    let _result = fs::remove_dir_all(&dir);
+    set_data_dir(dir.clone()).unwrap();
+    crate::write::create_log().unwrap();
+    (guard, dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_files_are_empty_and_broken_ones_are_errors() {
+        let (_guard, dir) = test_data_dir("files");
+        let outbox: Vec<String> = read_file("outbox.json").unwrap();
+        assert!(outbox.is_empty());
+        fs::write(dir.join("outbox.json"), "[\"cut").unwrap();
+        let err = read_file::<Vec<String>>("outbox.json").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read_to_string(dir.join("outbox.json")).unwrap(), "[\"cut");
+    }
+
+    #[test]
+    fn written_files_read_back() {
+        let (_guard, dir) = test_data_dir("write");
+        write_file("receipts.json", &vec![1, 2, 3]).unwrap();
+        write_file("receipts.json", &vec![4]).unwrap();
+        assert_eq!(read_file::<Vec<u32>>("receipts.json").unwrap(), vec![4]);
+        assert!(!dir.join("receipts.json.tmp").exists());
+    }
+}
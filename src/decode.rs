@@ -1,50 +1,399 @@
-pub enum DecodeMode {
-    Name,
-    Message,
-}
-
-//function to extract the sender name and message from a hex output of a bundle
-pub fn extract_source_message(the_string: String) -> Vec<String> {
-    let mut output = Vec::new();
-    let name_start = "201652f2f".to_string();
-    let name_end = "2f8201652f".to_string();
-    let text = get_text_between_strings(the_string.clone(), name_start, name_end,DecodeMode::Name);
-    output.push(decode_hex(text));
-    let message_start = "01010000".to_string();
-    let message_end = "0aff".to_string();
-    let text = get_text_between_strings(the_string, message_start, message_end,DecodeMode::Message);
-    let mut decoded_hex = decode_hex(text);
-
-    //when message > 31, 
-    //2 more hexadecimal digits are allocated in between message and message start, 
-    //which means another ascii letter is infront of the text
-    //so remove(0) when length > 31
-    if decoded_hex.len() > 31{
-        decoded_hex.remove(0);
-    }
-    output.push(decoded_hex);
-    output
-}
-
-//function to return a String between 2 Strings
-fn get_text_between_strings(s: String, start: String, end: String, mode: DecodeMode) -> String {
-
-    if let Some(start_idx) = s.find(&start) {
-        if let Some(end_idx) = s[start_idx..].find(&end){ 
-            match mode{
-                DecodeMode::Name => {return s[start_idx + start.len()..start_idx + end_idx].to_string();}
-
-                //remove first 2 hexadecimal digits
-                DecodeMode::Message => {return s[start_idx + start.len() + 2..start_idx + end_idx].to_string();}
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use bp7::canonical::CanonicalData;
+use bp7::crc::CrcBlock;
+use bp7::{Bundle, CreationTimestamp};
+use serde::{Deserialize, Serialize};
+use serde_cbor::Value;
+use crate::eid::Eid;
+use crate::envelope::Format;
+
+//bytes of a binary payload shown in the chat log
+const BINARY_PREVIEW: usize = 16;
+
+//seconds from the unix epoch to the dtn epoch, 2000-01-01 00:00:00 utc
+const DTN_EPOCH: u64 = 946_684_800;
+//the only bundle protocol version we read, bpv7 from rfc 9171
+const BP_VERSION: i128 = 7;
+
+//why a bundle could not be read
+#[derive(Debug, Clone)]
+pub enum DecodeError {
+    //the bytes end in the middle of the bundle
+    Truncated,
+    //the bytes are not cbor or not laid out like a bundle
+    Cbor(String),
+    //the primary block is of another bundle protocol version
+    UnsupportedVersion(i128),
+    //the bundle has no payload block to take a message from
+    NoPayload,
+    //the crc of the block with this number does not match its contents, 0 is the primary block
+    CrcMismatch(u64),
+    //the payload is not utf-8 text, it has this many bytes
+    NonTextPayload(usize),
+    //a fragment whose piece does not fit the whole payload, or that tells another length for it
+    //than the fragments of the same bundle before it
+    BadFragment { offset: u64, length: u64, total: u64 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "bundle is truncated"),
+            DecodeError::Cbor(err) => write!(f, "not a valid bundle: {}", err),
+            DecodeError::UnsupportedVersion(version) => write!(f, "bundle protocol version {} is not supported", version),
+            DecodeError::NoPayload => write!(f, "bundle has no payload block"),
+            DecodeError::CrcMismatch(0) => write!(f, "crc of the primary block does not match"),
+            DecodeError::CrcMismatch(block) => write!(f, "crc of block {} does not match", block),
+            DecodeError::NonTextPayload(size) => write!(f, "payload of {} bytes is not text", size),
+            DecodeError::BadFragment { offset, length, total } => {
+                write!(f, "fragment of {} bytes at offset {} does not fit a payload of {} bytes", length, offset, total)
             }
         }
     }
-    "Error: String Not found".to_string()
 }
 
-//function to decode a hex value into a ascii text
-pub fn decode_hex(hex: String) -> String{
-    let byte_vec = hex::decode(hex).unwrap();
-    String::from_utf8(byte_vec).unwrap()
+impl Error for DecodeError {}
+
+//what the crcs of the blocks of a bundle say about it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Integrity {
+    //no block carries a crc, so there is nothing to check
+    #[default]
+    Unchecked,
+    //every block with a crc matches it
+    Verified,
+    //the crc of the block with this number does not match its contents, 0 is the primary block
+    Corrupted(u64),
+}
+
+impl Integrity {
+    //the mismatch as a decode error, None unless corrupted
+    pub fn error(self) -> Option<DecodeError> {
+        match self {
+            Integrity::Corrupted(block) => Some(DecodeError::CrcMismatch(block)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Integrity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Integrity::Unchecked => write!(f, "no crc to check"),
+            Integrity::Verified => write!(f, "crc checked"),
+            Integrity::Corrupted(block) => write!(f, "{}", DecodeError::CrcMismatch(*block)),
+        }
+    }
+}
+
+//what the client needs of a bundle the daemon hands it, read from its cbor
+pub struct DecodedBundle {
+    pub source: Eid,
+    pub destination: Eid,
+    //dtn time and sequence number the bundle was created with
+    pub created: CreationTimestamp,
+    pub lifetime: Duration,
+    //status reports and other messages between daemons, not chat messages
+    pub administrative_record: bool,
+    //most hops the bundle may take and the hops it took, from its hop count block
+    pub hop_count: Option<(u8, u8)>,
+    //node that passed the bundle on to us, from its previous node block
+    pub previous_node: Option<Eid>,
+    pub integrity: Integrity,
+    //offset of the payload into the whole payload and the length of that, for fragments
+    pub fragment: Option<(u64, u64)>,
+    pub payload: Vec<u8>,
+}
+
+//when a received bundle was created and how it travelled, kept with its message in chat.json
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Metadata {
+    //dtn time in milliseconds since 2000 the bundle was created at, 0 if its creator had no clock
+    pub created: u64,
+    //tells apart bundles of the same source created in the same millisecond
+    pub sequence: u64,
+    //seconds the bundle may travel
+    pub lifetime: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hops: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hop_limit: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_node: Option<String>,
+    //unix time in seconds the bundle arrived
+    pub received: u64,
+    //messages received before crcs were checked read as unchecked
+    #[serde(default)]
+    pub integrity: Integrity,
+    //name the sender goes by and unix time in milliseconds they sent at, from the envelope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sent: Option<u64>,
+    //what the payload was, None in logs from before it was kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
 }
 
+impl Metadata {
+    //unix time in seconds the bundle was created at, None if its creator had no clock
+    pub fn created_unix(&self) -> Option<u64> {
+        (self.created != 0).then(|| self.created / 1000 + DTN_EPOCH)
+    }
+}
+
+impl DecodedBundle {
+    //node name of the sender, for both dtn: and ipn: nodes
+    pub fn sender(&self) -> String {
+        self.source.name()
+    }
+
+    //the metadata of the bundle, received now
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            created: self.created.dtntime(),
+            sequence: self.created.seqno(),
+            lifetime: self.lifetime.as_secs(),
+            hops: self.hop_count.map(|(_limit, count)| count),
+            hop_limit: self.hop_count.map(|(limit, _count)| limit),
+            previous_node: self.previous_node.as_ref().map(Eid::to_string),
+            received: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            integrity: self.integrity,
+            display_name: None,
+            sent: None,
+            format: None,
+        }
+    }
+
+    //the payload as text, without the newline messages are sent with
+    pub fn text(&self) -> Result<&str, DecodeError> {
+        let text = std::str::from_utf8(&self.payload)
+            .map_err(|_| DecodeError::NonTextPayload(self.payload.len()))?;
+        Ok(text.strip_suffix('\n').unwrap_or(text))
+    }
+
+    //the chat message in the payload, payloads that are not text are described instead, with their first bytes in hex
+    pub fn message(&self) -> String {
+        match self.text() {
+            Ok(text) => text.to_string(),
+            Err(_) => {
+                let shown = &self.payload[..self.payload.len().min(BINARY_PREVIEW)];
+                let more = if self.payload.len() > BINARY_PREVIEW { "\u{2026}" } else { "" };
+                format!("[binary payload, {} bytes: {}{}]", self.payload.len(), hex::encode(shown), more)
+            }
+        }
+    }
+}
+
+//parses a whole bpv7 bundle: the primary block, any extension blocks and the payload block,
+//and checks the crcs of the blocks that carry one. a crc mismatch is left for the caller to flag
+pub fn decode(bytes: &[u8]) -> Result<DecodedBundle, DecodeError> {
+    //a bundle is an array of blocks, the first one the primary block starting with the version
+    let blocks = match serde_cbor::from_slice(bytes) {
+        Ok(Value::Array(blocks)) => blocks,
+        Ok(_) => return Err(DecodeError::Cbor(String::from("bundle is not an array of blocks"))),
+        Err(err) if err.is_eof() => return Err(DecodeError::Truncated),
+        Err(err) => return Err(DecodeError::Cbor(err.to_string())),
+    };
+    if let Some(Value::Array(primary)) = blocks.first() {
+        match primary.first() {
+            Some(Value::Integer(BP_VERSION)) => {}
+            Some(Value::Integer(version)) => return Err(DecodeError::UnsupportedVersion(*version)),
+            _ => {}
+        }
+    }
+    let mut bundle = Bundle::try_from(bytes).map_err(|err| DecodeError::Cbor(err.to_string()))?;
+    let integrity = check_crcs(&mut bundle);
+    let payload = match bundle.extension_block_by_type(bp7::PAYLOAD_BLOCK).map(|block| block.data()) {
+        Some(CanonicalData::Data(payload)) => payload.clone(),
+        _ => return Err(DecodeError::NoPayload),
+    };
+    let hop_count = match bundle.extension_block_by_type(bp7::HOP_COUNT_BLOCK).map(|block| block.data()) {
+        Some(CanonicalData::HopCount(limit, count)) => Some((*limit, *count)),
+        _ => None,
+    };
+    let previous_node = match bundle.extension_block_by_type(bp7::PREVIOUS_NODE_BLOCK).map(|block| block.data()) {
+        Some(CanonicalData::PreviousNode(node)) => Some(node.clone().into()),
+        _ => None,
+    };
+    Ok(DecodedBundle {
+        administrative_record: bundle.is_administrative_record(),
+        hop_count,
+        previous_node,
+        integrity,
+        fragment: bundle.primary.has_fragmentation()
+            .then_some((bundle.primary.fragmentation_offset, bundle.primary.total_data_length)),
+        source: bundle.primary.source.into(),
+        destination: bundle.primary.destination.into(),
+        created: bundle.primary.creation_timestamp,
+        lifetime: bundle.primary.lifetime,
+        payload,
+    })
+}
+
+//checks the crc16 or crc32 of the primary block and of every canonical block that carries one, as rfc 9171 asks
+fn check_crcs(bundle: &mut Bundle) -> Integrity {
+    let mut checked = false;
+    if bundle.primary.has_crc() {
+        if !bundle.primary.check_crc() {
+            return Integrity::Corrupted(0);
+        }
+        checked = true;
+    }
+    for block in bundle.canonicals.iter_mut().filter(|block| block.has_crc()) {
+        if !block.check_crc() {
+            return Integrity::Corrupted(block.block_number);
+        }
+        checked = true;
+    }
+    if checked { Integrity::Verified } else { Integrity::Unchecked }
+}
+
+#[cfg(test)]
+mod tests {
+    use bp7::canonical;
+    use bp7::flags::BlockControlFlags;
+    use crate::bundle;
+    use crate::options::SendOptions;
+    use super::*;
+
+    //endpoint of the inbox on the node named name, ipn: nodes only have numbered services
+    fn inbox(name: &str) -> Eid {
+        let service = if name.starts_with("ipn:") { "1" } else { "incoming" };
+        Eid::from_name(name).unwrap().service(service).unwrap()
+    }
+
+    //a bundle from the inbox of node source to the inbox of node destination, as the client sends it
+    fn build(source: &str, destination: &str, payload: Vec<u8>) -> Bundle {
+        let source = inbox(source);
+        let destination = inbox(destination);
+        bundle::build(&source, &destination, &source, payload, &SendOptions::default())
+    }
+
+    //decodes bundle and checks it reads back as it was built
+    fn round_trip(mut bundle: Bundle) -> DecodedBundle {
+        let decoded = decode(&bundle.to_cbor()).unwrap();
+        assert_eq!(decoded.source, Eid::from(bundle.primary.source.clone()));
+        assert_eq!(decoded.destination, Eid::from(bundle.primary.destination.clone()));
+        assert_eq!(decoded.created, bundle.primary.creation_timestamp);
+        assert_eq!(decoded.lifetime, bundle.primary.lifetime);
+        assert_eq!(Some(&decoded.payload), bundle.payload());
+        assert_eq!(decoded.fragment, None);
+        assert!(!decoded.administrative_record);
+        decoded
+    }
+
+    #[test]
+    fn node_names_read_back() {
+        let long = "node-".repeat(60);
+        for name in ["a", "alice", "node-7.lab_2", long.as_str(), "ipn:1", "ipn:18446744073709551615"] {
+            let decoded = round_trip(build(name, "bob", b"hello\n".to_vec()));
+            assert_eq!(decoded.sender(), name);
+            assert_eq!(decoded.text().unwrap(), "hello");
+        }
+    }
+
+    #[test]
+    fn payload_lengths_around_cbor_length_prefixes() {
+        //byte strings get a longer length prefix from 24, 256 and 65536 bytes on
+        for length in [0, 23, 24, 255, 256, 65535, 65536, 70_000] {
+            let payload: Vec<u8> = (0..length).map(|byte| (byte % 251) as u8).collect();
+            let decoded = round_trip(build("alice", "bob", payload.clone()));
+            assert_eq!(decoded.payload, payload);
+        }
+    }
+
+    #[test]
+    fn extension_blocks_are_read_or_skipped() {
+        let mut bundle = build("alice", "bob", b"with extras".to_vec());
+        let carol: bp7::EndpointID = Eid::from_name("carol").unwrap().into();
+        bundle.add_canonical_block(canonical::new_previous_node_block(0, BlockControlFlags::empty(), carol));
+        bundle.add_canonical_block(canonical::new_canonical_block(200, 0, 0, canonical::CanonicalData::Unknown(vec![1, 2, 3])));
+        bundle.set_crc(bp7::crc::CRC_32);
+        let decoded = round_trip(bundle);
+        assert_eq!(decoded.previous_node, Some(Eid::from_name("carol").unwrap()));
+        assert_eq!(decoded.hop_count, Some((32, 0)));
+        assert_eq!(decoded.payload, b"with extras");
+    }
+
+    #[test]
+    fn truncated_bundles() {
+        let bytes = build("alice", "bob", b"cut short".to_vec()).to_cbor();
+        for length in [1, bytes.len() / 2, bytes.len() - 1] {
+            assert!(matches!(decode(&bytes[..length]), Err(DecodeError::Truncated)), "cut at {}", length);
+        }
+    }
+
+    #[test]
+    fn bytes_that_are_not_a_bundle() {
+        assert!(matches!(decode(&[0xff, 0x00]), Err(DecodeError::Cbor(_))));
+        assert!(matches!(decode(b"hello, not a bundle"), Err(DecodeError::Cbor(_))));
+        assert!(matches!(decode(&[0x9f, 0x01, 0xff]), Err(DecodeError::Cbor(_))));
+    }
+
+    #[test]
+    fn other_bundle_protocol_versions() {
+        let bytes = build("alice", "bob", b"from the past".to_vec()).to_cbor();
+        let Ok(Value::Array(mut blocks)) = serde_cbor::from_slice::<Value>(&bytes) else { panic!("not a bundle") };
+        let Some(Value::Array(primary)) = blocks.first_mut() else { panic!("no primary block") };
+        primary[0] = Value::Integer(6);
+        let bytes = serde_cbor::to_vec(&Value::Array(blocks)).unwrap();
+        assert!(matches!(decode(&bytes), Err(DecodeError::UnsupportedVersion(6))));
+    }
+
+    //bytes with one bit flipped in the first occurrence of part, which stays valid cbor of the same length
+    fn flip(bytes: &[u8], part: &[u8]) -> Vec<u8> {
+        let at = bytes.windows(part.len()).position(|window| window == part).unwrap();
+        let mut flipped = bytes.to_vec();
+        flipped[at] ^= 0x01;
+        flipped
+    }
+
+    #[test]
+    fn crcs_of_untouched_bundles_match() {
+        let decoded = decode(&build("alice", "bob", b"all there".to_vec()).to_cbor()).unwrap();
+        assert_eq!(decoded.integrity, Integrity::Verified);
+    }
+
+    #[test]
+    fn flipped_bytes_are_found_in_their_block() {
+        let bytes = build("alice", "bob", b"all there".to_vec()).to_cbor();
+        //the source is in the primary block, the text in the payload block
+        let decoded = decode(&flip(&bytes, b"alice")).unwrap();
+        assert_eq!(decoded.integrity, Integrity::Corrupted(0));
+        let decoded = decode(&flip(&bytes, b"all there")).unwrap();
+        assert_eq!(decoded.integrity, Integrity::Corrupted(1));
+        assert_eq!(decoded.payload, b"`ll there");
+    }
+
+    #[test]
+    fn bundles_without_crcs_are_unchecked() {
+        let mut bundle = build("alice", "bob", b"no crcs".to_vec());
+        bundle.set_crc(bp7::crc::CRC_NO);
+        let decoded = decode(&flip(&bundle.to_cbor(), b"no crcs")).unwrap();
+        assert_eq!(decoded.integrity, Integrity::Unchecked);
+        assert_eq!(decoded.payload, b"oo crcs");
+    }
+
+    #[test]
+    fn messages_lose_one_trailing_newline() {
+        let decoded = decode(&build("alice", "bob", b"two lines\n\n".to_vec()).to_cbor()).unwrap();
+        assert_eq!(decoded.text().unwrap(), "two lines\n");
+        assert_eq!(decoded.message(), "two lines\n");
+        let decoded = decode(&build("alice", "bob", b"no newline".to_vec()).to_cbor()).unwrap();
+        assert_eq!(decoded.message(), "no newline");
+    }
+
+    #[test]
+    fn binary_payloads_are_described() {
+        let decoded = decode(&build("alice", "bob", vec![0xff, 0xfe, 0x00]).to_cbor()).unwrap();
+        assert!(matches!(decoded.text(), Err(DecodeError::NonTextPayload(3))));
+        assert_eq!(decoded.message(), "[binary payload, 3 bytes: fffe00]");
+        let payload: Vec<u8> = (0xe0..=0xff).collect();
+        let decoded = decode(&build("alice", "bob", payload).to_cbor()).unwrap();
+        assert_eq!(decoded.message(), "[binary payload, 32 bytes: e0e1e2e3e4e5e6e7e8e9eaebecedeeef\u{2026}]");
+    }
+}
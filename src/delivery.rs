@@ -0,0 +1,86 @@
+use std::fmt;
+use bp7::administrative_record::{self, AdministrativeRecord};
+use serde::{Deserialize, Serialize};
+use crate::decode::DecodedBundle;
+
+//how far a sent message got, as far as status reports told us
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryState {
+    //the daemon did not take the bundle and never will
+    Failed,
+    //the daemon could not be reached yet, the outbox keeps trying
+    Pending,
+    //the daemon took the bundle and holds it until it can forward it
+    Queued,
+    //another node got the bundle or passed it on
+    Forwarded,
+    Delivered,
+    //a node dropped the bundle, expired ones have their own state
+    Deleted,
+    Expired,
+    //the peer opened the conversation, told by their read receipt
+    Read,
+}
+
+impl fmt::Display for DeliveryState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeliveryState::Failed => write!(f, "failed"),
+            DeliveryState::Pending => write!(f, "pending"),
+            DeliveryState::Queued => write!(f, "queued"),
+            DeliveryState::Forwarded => write!(f, "forwarded"),
+            DeliveryState::Delivered => write!(f, "delivered"),
+            DeliveryState::Deleted => write!(f, "deleted"),
+            DeliveryState::Expired => write!(f, "expired"),
+            DeliveryState::Read => write!(f, "read"),
+        }
+    }
+}
+
+impl DeliveryState {
+    //the state after a report saying next. reports arrive in any order, so a state never goes back,
+    //and a delivered bundle stays delivered even if a copy of it is dropped somewhere else
+    pub fn advance(self, next: DeliveryState) -> DeliveryState {
+        if next.rank() > self.rank() { next } else { self }
+    }
+
+    fn rank(self) -> u8 {
+        match self {
+            DeliveryState::Failed | DeliveryState::Pending => 0,
+            DeliveryState::Queued => 1,
+            DeliveryState::Forwarded => 2,
+            DeliveryState::Deleted | DeliveryState::Expired => 3,
+            DeliveryState::Delivered => 4,
+            DeliveryState::Read => 5,
+        }
+    }
+}
+
+//id of the bundle a status report is about and the state it reports, None for any other bundle
+pub fn status_report(bundle: &DecodedBundle) -> Option<(String, DeliveryState)> {
+    if !bundle.administrative_record {
+        return None;
+    }
+    let report = match serde_cbor::from_slice(&bundle.payload).ok()? {
+        AdministrativeRecord::BundleStatusReport(report) => report,
+        _ => return None,
+    };
+    let asserted = |position: u32| {
+        report.status_information.get(position as usize).is_some_and(|item| item.asserted)
+    };
+    let state = if asserted(administrative_record::DELIVERED_BUNDLE) {
+        DeliveryState::Delivered
+    } else if asserted(administrative_record::DELETED_BUNDLE) {
+        if report.report_reason == administrative_record::LIFETIME_EXPIRED {
+            DeliveryState::Expired
+        } else {
+            DeliveryState::Deleted
+        }
+    } else if asserted(administrative_record::FORWARDED_BUNDLE) || asserted(administrative_record::RECEIVED_BUNDLE) {
+        DeliveryState::Forwarded
+    } else {
+        return None;
+    };
+    Some((report.refbundle(), state))
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::str::FromStr;
+use bp7::eid::EndpointIdError;
+use bp7::EndpointID;
+
+//endpoint id of a node or one of its services, in the dtn: or the ipn: scheme
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Eid(EndpointID);
+
+impl Eid {
+    //node id of the node shown as name, the reverse of name()
+    pub fn from_name(name: &str) -> Result<Eid, EndpointIdError> {
+        let name = name.trim();
+        match name.strip_prefix("ipn:") {
+            Some(number) => Ok(Eid(EndpointID::with_ipn(number.parse()?, 0)?)),
+            None => Ok(Eid(EndpointID::try_from(format!("dtn://{}/", name))?)),
+        }
+    }
+
+    //name a node is listed and logged under, the node name for dtn: nodes and ipn:(number) for ipn: nodes
+    pub fn name(&self) -> String {
+        match &self.0 {
+            EndpointID::Ipn(_, address) => format!("ipn:{}", address.node_number()),
+            eid => eid.node().unwrap_or_else(|| eid.to_string()),
+        }
+    }
+
+    //endpoint of service on the same node, ipn: nodes only have numbered services
+    pub fn service(&self, service: &str) -> Result<Eid, EndpointIdError> {
+        Ok(Eid(self.0.new_endpoint(service)?))
+    }
+}
+
+impl From<EndpointID> for Eid {
+    fn from(eid: EndpointID) -> Self {
+        Eid(eid)
+    }
+}
+
+impl From<Eid> for EndpointID {
+    fn from(eid: Eid) -> Self {
+        eid.0
+    }
+}
+
+impl FromStr for Eid {
+    type Err = EndpointIdError;
+
+    fn from_str(eid: &str) -> Result<Self, Self::Err> {
+        Ok(Eid(EndpointID::try_from(eid.trim())?))
+    }
+}
+
+impl fmt::Display for Eid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
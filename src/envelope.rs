@@ -0,0 +1,133 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+//version of the envelope layout this client writes
+pub const VERSION: u32 = 1;
+
+//what a received payload was
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    //an envelope of this client
+    Envelope,
+    //bare utf-8 text, as dtnsend and most other tools send
+    Text,
+    //anything else, shown as its size and first bytes
+    Binary,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Envelope => write!(f, "envelope"),
+            Format::Text => write!(f, "plain text"),
+            Format::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+//what the client puts in the payload of its bundles, as a cbor map
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Envelope {
+    //layout version, newer ones only add kinds and fields
+    pub version: u32,
+    //random uuid of the message, see write::new_id()
+    pub id: String,
+    //name the sender goes by, their node name unless they set one
+    pub sender: String,
+    //unix time in milliseconds the sender sent it at
+    pub sent: u64,
+    //id of the message this one answers, it may not have arrived yet
+    #[serde(default, rename = "reply-to", skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    #[serde(flatten)]
+    pub kind: Kind,
+}
+
+//what an envelope carries, written as "kind" with the content as "body"
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", content = "body", rename_all = "kebab-case")]
+pub enum Kind {
+    //a chat message
+    Text(String),
+    //the messages with these ids reached the sender's client
+    Ack { ids: Vec<String> },
+    //the sender opened the conversation with the messages with these ids
+    Read { ids: Vec<String> },
+    //an emoji in answer to the message with this id
+    Reaction { id: String, emoji: String },
+    //a piece of the file name, starting offset bytes into it
+    FileChunk {
+        name: String,
+        offset: u64,
+        total: u64,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+    //how the sender is doing, like "away"
+    Presence { status: String },
+    //a kind of a newer version, named as it was written
+    #[serde(skip)]
+    Unknown(String),
+}
+
+//the fields every version has, to read envelopes of kinds this one doesn't know
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+    id: String,
+    sender: String,
+    sent: u64,
+    kind: String,
+}
+
+impl Envelope {
+    //envelope of kind for the message id, sent now by sender
+    pub fn new(id: &str, sender: &str, kind: Kind) -> Envelope {
+        Envelope {
+            version: VERSION,
+            id: id.to_string(),
+            sender: sender.to_string(),
+            sent: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            reply_to: None,
+            kind,
+        }
+    }
+
+    //the envelope in payload, None if it doesn't hold one
+    pub fn read(payload: &[u8]) -> Option<Envelope> {
+        if let Ok(envelope) = serde_cbor::from_slice(payload) {
+            return Some(envelope);
+        }
+        let header: Header = serde_cbor::from_slice(payload).ok()?;
+        Some(Envelope {
+            version: header.version,
+            id: header.id,
+            sender: header.sender,
+            sent: header.sent,
+            reply_to: None,
+            kind: Kind::Unknown(header.kind),
+        })
+    }
+
+    pub fn to_cbor(&self) -> Vec<u8> {
+        serde_cbor::to_vec(self).expect("envelopes only hold strings, numbers and bytes")
+    }
+
+    //how the envelope reads in the chat log
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            Kind::Text(text) => text.clone(),
+            Kind::Ack { ids } => format!("[got {} of your messages]", ids.len()),
+            Kind::Read { ids } => format!("[read {} of your messages]", ids.len()),
+            Kind::Reaction { emoji, .. } => format!("[reacted {}]", emoji),
+            Kind::FileChunk { name, offset, total, data } => {
+                format!("[part of file {}, bytes {} to {} of {}, files are not supported yet]",
+                        name, offset, offset + data.len() as u64, total)
+            }
+            Kind::Presence { status } => format!("[is {}]", status),
+            Kind::Unknown(kind) => format!("[a {} message from envelope version {}, which this client can't show]", kind, self.version),
+        }
+    }
+}
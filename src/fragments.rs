@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::config;
+use crate::decode::{DecodeError, DecodedBundle, Integrity};
+
+//fragments of bundles that are not complete yet, kept across restarts
+const FRAGMENTS_FILE: &str = "fragments.json";
+
+//subscriptions of several endpoints may get fragments at the same time
+static FRAGMENTS: Mutex<()> = Mutex::new(());
+
+//the fragments of one bundle received so far
+#[derive(Debug, Deserialize, Serialize)]
+struct Partial {
+    //node name of the sender
+    sender: String,
+    //endpoint the fragments arrive on
+    endpoint: String,
+    //length of the whole payload in bytes
+    total: u64,
+    //unix time in seconds the bundle expires, its fragments are dropped then
+    expires: u64,
+    //worst crc check of any fragment so far
+    integrity: Integrity,
+    //payload pieces in hex, by offset into the whole payload
+    pieces: BTreeMap<u64, String>,
+}
+
+impl Partial {
+    //bytes of the whole payload covered by the pieces, overlapping pieces counted once.
+    //pieces reaching past the end only count up to it, so all of it is there once this is total
+    fn received(&self) -> u64 {
+        let mut covered = 0;
+        let mut end = 0;
+        for (offset, piece) in self.pieces.iter() {
+            let piece_end = offset.saturating_add(piece.len() as u64 / 2).min(self.total);
+            let start = end.max(*offset);
+            if piece_end > start {
+                covered += piece_end - start;
+                end = piece_end;
+            }
+        }
+        covered
+    }
+
+    //the whole payload once the pieces cover all of it without gaps
+    fn assemble(&self) -> Option<Vec<u8>> {
+        if self.received() < self.total {
+            return None;
+        }
+        let mut payload = vec![0; self.total as usize];
+        for (offset, piece) in self.pieces.iter() {
+            let bytes = hex::decode(piece).ok()?;
+            let start = (*offset as usize).min(payload.len());
+            let end = (start + bytes.len()).min(payload.len());
+            payload[start..end].copy_from_slice(&bytes[..end - start]);
+        }
+        Some(payload)
+    }
+}
+
+//how much of a fragmented bundle has arrived
+#[derive(Debug, Clone)]
+pub struct Progress {
+    //source and creation time of the bundle, the same for all its fragments
+    pub id: String,
+    pub sender: String,
+    pub endpoint: String,
+    pub received: u64,
+    pub total: u64,
+}
+
+//bundles are told apart by source and creation time, as rfc 9171 does
+fn bundle_id(fragment: &DecodedBundle) -> String {
+    format!("{} {} {}", fragment.source, fragment.created.dtntime(), fragment.created.seqno())
+}
+
+//whether the piece of fragment lies within the whole payload and its length for it matches
+//the one the fragments of the same bundle in partials told
+fn fits(fragment: &DecodedBundle, partials: &BTreeMap<String, Partial>) -> Result<(), DecodeError> {
+    let (offset, total) = fragment.fragment.unwrap_or((0, fragment.payload.len() as u64));
+    let length = fragment.payload.len() as u64;
+    let known = partials.get(&bundle_id(fragment)).map_or(total, |partial| partial.total);
+    match offset.checked_add(length) {
+        Some(end) if end <= total && total == known => Ok(()),
+        _ => Err(DecodeError::BadFragment { offset, length, total }),
+    }
+}
+
+//the error a fragment that can't be part of its bundle is quarantined with, if it is one
+pub fn check(fragment: &DecodedBundle) -> Result<(), DecodeError> {
+    let _guard = FRAGMENTS.lock().unwrap();
+    //an unreadable fragments.json is left to add(), which fails without writing over it
+    fits(fragment, &load().unwrap_or_default())
+}
+
+//adds a fragment that arrived on endpoint to fragments.json and tells how much of its bundle is there,
+//with the bundle carrying the whole payload if this was the last missing piece.
+//fragments check() turns down are refused
+pub fn add(mut fragment: DecodedBundle, endpoint: &str) -> io::Result<(Progress, Option<DecodedBundle>)> {
+    let _guard = FRAGMENTS.lock().unwrap();
+    let (offset, total) = fragment.fragment.unwrap_or((0, fragment.payload.len() as u64));
+    let id = bundle_id(&fragment);
+    let mut partials = load()?;
+    fits(&fragment, &partials).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let now = config::now();
+    partials.retain(|_id, partial| partial.expires > now);
+    let partial = partials.entry(id.clone()).or_insert_with(|| Partial {
+        sender: fragment.sender(),
+        endpoint: endpoint.to_string(),
+        total,
+        expires: now + fragment.lifetime.as_secs(),
+        integrity: fragment.integrity,
+        pieces: BTreeMap::new(),
+    });
+    partial.pieces.insert(offset, hex::encode(&fragment.payload));
+    //one bad fragment makes the whole payload suspect
+    partial.integrity = match (partial.integrity, fragment.integrity) {
+        (Integrity::Corrupted(block), _) | (_, Integrity::Corrupted(block)) => Integrity::Corrupted(block),
+        (Integrity::Verified, Integrity::Verified) => Integrity::Verified,
+        _ => Integrity::Unchecked,
+    };
+    let progress = Progress {
+        id: id.clone(),
+        sender: partial.sender.clone(),
+        endpoint: partial.endpoint.clone(),
+        received: partial.received(),
+        total: partial.total,
+    };
+    let complete = partial.assemble().map(|payload| {
+        fragment.integrity = partial.integrity;
+        fragment.payload = payload;
+        fragment.fragment = None;
+        fragment
+    });
+    if complete.is_some() {
+        partials.remove(&id);
+    }
+    save(&partials)?;
+    Ok((progress, complete))
+}
+
+//progress of every bundle still missing pieces, to show after a restart
+pub fn waiting() -> io::Result<Vec<Progress>> {
+    let _guard = FRAGMENTS.lock().unwrap();
+    let now = config::now();
+    Ok(load()?.into_iter()
+        .filter(|(_id, partial)| partial.expires > now)
+        .map(|(id, partial)| Progress {
+            received: partial.received(),
+            total: partial.total,
+            id,
+            sender: partial.sender,
+            endpoint: partial.endpoint,
+        })
+        .collect())
+}
+
+//reads fragments.json, there are no fragments waiting without one
+fn load() -> io::Result<BTreeMap<String, Partial>> {
+    config::read_file(FRAGMENTS_FILE)
+}
+
+fn save(partials: &BTreeMap<String, Partial>) -> io::Result<()> {
+    config::write_file(FRAGMENTS_FILE, partials)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use bp7::CreationTimestamp;
+    use crate::eid::Eid;
+    use super::*;
+
+    fn partial(total: u64, pieces: &[(u64, usize)]) -> Partial {
+        Partial {
+            sender: String::from("alice"),
+            endpoint: String::from("incoming"),
+            total,
+            expires: u64::MAX,
+            integrity: Integrity::Unchecked,
+            pieces: pieces.iter().map(|(offset, length)| (*offset, "ab".repeat(*length))).collect(),
+        }
+    }
+
+    fn fragment(offset: u64, length: usize, total: u64) -> DecodedBundle {
+        let source = Eid::from_name("alice").unwrap();
+        DecodedBundle {
+            destination: source.clone(),
+            source,
+            created: CreationTimestamp::with_time_and_seq(1, 0),
+            lifetime: Duration::from_secs(60),
+            administrative_record: false,
+            hop_count: None,
+            previous_node: None,
+            integrity: Integrity::Unchecked,
+            fragment: Some((offset, total)),
+            payload: vec![0xab; length],
+        }
+    }
+
+    #[test]
+    fn overlapping_pieces_count_once() {
+        let partial = partial(100, &[(0, 60), (40, 60)]);
+        assert_eq!(partial.received(), 100);
+        assert_eq!(partial.assemble(), Some(vec![0xab; 100]));
+    }
+
+    #[test]
+    fn pieces_past_the_end_dont_fill_gaps() {
+        let partial = partial(100, &[(0, 50), (60, 60)]);
+        assert_eq!(partial.received(), 90);
+        assert_eq!(partial.assemble(), None);
+        assert_eq!(self::partial(100, &[(150, 10)]).received(), 0);
+    }
+
+    #[test]
+    fn fragments_must_fit_their_bundle() {
+        let mut partials = BTreeMap::new();
+        assert!(fits(&fragment(0, 50, 100), &partials).is_ok());
+        assert!(fits(&fragment(60, 60, 100), &partials).is_err());
+        assert!(fits(&fragment(u64::MAX - 4, 10, 100), &partials).is_err());
+        partials.insert(bundle_id(&fragment(0, 50, 100)), partial(100, &[(0, 50)]));
+        assert!(fits(&fragment(50, 50, 200), &partials).is_err());
+        assert!(fits(&fragment(50, 50, 100), &partials).is_ok());
+    }
+}
@@ -3,11 +3,12 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::fs::File;
-use std::io::{self, Read, Write};
+use std::io;
 use std::thread;
-use std::sync::mpsc;
-use std::collections::HashMap;
+use std::process::Child;
+use std::sync::{mpsc, Mutex};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::time::{Duration, Instant};
 use std::{error::Error};
 use tui::{
@@ -20,37 +21,87 @@ use tui::{
 };
 use core::result;
 use unicode_width::UnicodeWidthStr;
-use serde::{Deserialize, Serialize};
-use serde_json::{Map, Value};
+use dtn7::DtnPeer;
+use dtn7::core::peer::PeerType;
+mod config;
 mod write;
 mod decode;
 mod processes;
+mod transport;
+mod subscriber;
+mod worker;
+mod rooms;
+mod eid;
+mod options;
+mod bundle;
+mod delivery;
+mod outbox;
+mod store;
+mod mock;
+mod quarantine;
+mod fragments;
+mod envelope;
+mod receipts;
+use config::{Config, StaticPeer};
+use decode::DecodeError;
+use delivery::DeliveryState;
+use envelope::Format;
+use options::SendOptions;
+use eid::Eid;
+use transport::{DtndClient, Transport, TransportError};
+use store::StoredBundle;
+use write::Message;
 enum ChatMode {
     Idle,
     Chatting,
+    //looking through the bundles the daemon holds
+    Browsing,
+    //picking a message in the chat log of the selected conversation
+    Reading,
 }
 enum State {
     Near,
     Away,
+    //not a peer but one of our channel endpoints
+    Channel,
+    //a chat room on a group endpoint
+    Room,
 }
 enum AppEvent<I> {
     Input(I),
     Tick,
+    //node id of the daemon, sent by the worker once it is reachable
+    NodeId(Eid),
+    //latest peer list of the daemon, sent by the worker
+    PeerUpdate(BTreeMap<String, DtnPeer>),
+    //a message that arrived and is already logged in chat.json
+    IncomingMessage(Message),
+    //a message of ours as the worker sent and logged it, with the first error if a bundle could not be sent
+    SendResult(Message, Option<TransportError>),
+    //a message of ours after the outbox tried one of its bundles again, with the error if that failed for good
+    Retried(Message, Option<TransportError>),
+    //a status report moved the bundle with this id on to a new state
+    Delivery(String, DeliveryState),
+    //the bundles the daemon holds, as the worker read them for the store browser
+    Store(Result<Vec<StoredBundle>, TransportError>),
+    //a bundle arrived on the endpoint that could not be read and was put in quarantine.json
+    Quarantined(String, DecodeError),
+    //a fragment arrived, with how much of its bundle is there now
+    Fragment(fragments::Progress),
+    //a peer's client got or showed the messages of ours with these ids, as an ack or read receipt told
+    Receipt(Vec<String>, DeliveryState),
 }
 
-impl ToString for State {
-    fn to_string(&self) -> String {
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            State::Near => "Near".to_string(),
-            State::Away => "Away".to_string(),
+            State::Near => write!(f, "Near"),
+            State::Away => write!(f, "Away"),
+            State::Channel => write!(f, "Channel"),
+            State::Room => write!(f, "Room"),
         }
     }
 }
-#[derive(Debug, Deserialize, Serialize)]
-struct Message {
-    sender: String,
-    message: String,
-}
 
 struct SeenNode{
     name: String,
@@ -59,15 +110,60 @@ struct SeenNode{
 struct Application {
     input: String, 
     input_mode: ChatMode,
-    messages: Vec<String>,
+    messages: Vec<Message>,
+    //service name of our inbox, messages on any other endpoint belong to a channel
+    endpoint: String,
+    local_user: String,
+    peer_list_state: ListState,
+    seen_nodes: Vec<SeenNode>,
+    notifications: Vec<String>,
+    //last error reported by the worker, shown in the header
+    status: Option<String>,
+    //what the daemon last told about each peer, by the name in the peer list
+    peers: BTreeMap<String, DtnPeer>,
+    //whether the details of the selected peer are shown above the chat log
+    show_details: bool,
+    //bundles in the daemon's store when it was last read
+    stored: Vec<StoredBundle>,
+    store_state: ListState,
+    //whether the payload of the selected bundle is shown
+    inspecting: bool,
+    //number of bundles in quarantine.json
+    quarantined: usize,
+    //message picked in the chat log when reading, counted among the messages of the selected conversation
+    message_selected: usize,
+    //whether the details of the picked message are shown
+    show_message: bool,
+    //fragmented bundles still missing pieces
+    partials: Vec<fragments::Progress>,
+    //id of the message the one being typed answers
+    replying: Option<String>,
 }
 
 impl Default for Application {
     fn default() -> Application {
+        let mut peer_list_state = ListState::default(); // create a ListState to track selected node
+        peer_list_state.select(Some(0)); //select Some() initial value for ListState
         Application {
             input: String::new(),
             input_mode: ChatMode::Idle,
             messages: Vec::new(),
+            endpoint: String::from("incoming"),
+            local_user: String::new(),
+            peer_list_state,
+            seen_nodes: Vec::new(),
+            notifications: Vec::new(),
+            status: None,
+            peers: BTreeMap::new(),
+            show_details: false,
+            stored: Vec::new(),
+            store_state: ListState::default(),
+            inspecting: false,
+            quarantined: 0,
+            message_selected: 0,
+            show_message: false,
+            partials: Vec::new(),
+            replying: None,
         }
     }
 }
@@ -77,29 +173,289 @@ impl SeenNode {
     }
 }
 
+impl Application {
+    //adds newly seen peers and updates the states of all seen peers, Near if the daemon lists them, Away if otherwise
+    fn update_peers(&mut self, peers: BTreeMap<String, DtnPeer>) {
+        //peers are listed under the name of their node id, so dtn: and ipn: nodes can't clash
+        let names: Vec<String> = peers.values().map(|peer| Eid::from(peer.eid.clone()).name()).collect();
+        //peers out of reach keep their last details
+        for (name, peer) in names.iter().zip(peers.into_values()) {
+            self.peers.insert(name.clone(), peer);
+        }
+        for name in names.iter() {
+            if !self.seen_nodes.iter().any(|node| &node.name == name) {
+                self.seen_nodes.push(SeenNode::new(name.clone(), State::Near));
+            }
+        }
+        for node in self.seen_nodes.iter_mut() {
+            if let State::Channel | State::Room = node.state {
+                continue;
+            }
+            node.state = if names.contains(&node.name) { State::Near } else { State::Away };
+        }
+        self.sync_selection();
+    }
+
+    //points GLOBAL_SELECTED_NODE at the highlighted peer and clears its notification
+    fn sync_selection(&mut self) {
+        if let Some(node) = self.peer_list_state.selected().and_then(|index| self.seen_nodes.get(index)) {
+            *GLOBAL_SELECTED_NODE.lock().unwrap() = node.name.clone();
+            self.notifications.retain(|name| name != &node.name);
+        }
+    }
+
+    //name of the entry in the peer list a message belongs to, see Message::conversation()
+    fn conversation_of(&self, message: &Message) -> String {
+        message.conversation(&self.endpoint)
+    }
+
+    //the selected entry in the peer list
+    fn selected(&self) -> Option<&SeenNode> {
+        self.peer_list_state.selected().and_then(|index| self.seen_nodes.get(index))
+    }
+
+    //lists a room below the channels and rooms already there, false if it is listed already
+    fn add_room(&mut self, room: &str) -> bool {
+        let name = format!("~{}", room);
+        if self.seen_nodes.iter().any(|node| node.name == name) {
+            return false;
+        }
+        let position = self.seen_nodes.iter()
+            .take_while(|node| matches!(node.state, State::Channel | State::Room))
+            .count();
+        self.seen_nodes.insert(position, SeenNode::new(name, State::Room));
+        self.sync_selection();
+        true
+    }
+
+    //takes the selected room off the list and returns its name, None if no room is selected
+    fn remove_selected_room(&mut self) -> Option<String> {
+        let index = self.peer_list_state.selected()?;
+        if !matches!(self.seen_nodes.get(index)?.state, State::Room) {
+            return None;
+        }
+        let node = self.seen_nodes.remove(index);
+        self.notifications.retain(|name| name != &node.name);
+        self.peer_list_state.select(Some(index.min(self.seen_nodes.len().saturating_sub(1))));
+        if self.seen_nodes.is_empty() {
+            GLOBAL_SELECTED_NODE.lock().unwrap().clear();
+        }
+        self.sync_selection();
+        Some(node.name.trim_start_matches('~').to_string())
+    }
+
+    //applies a status report to the message sent in the bundle bundle_id
+    fn update_delivery(&mut self, bundle_id: &str, state: DeliveryState) {
+        for message in self.messages.iter_mut().filter(|message| message.sent_in(bundle_id)) {
+            message.advance(state);
+        }
+    }
+
+    //moves the messages of ours with these ids on to state, a peer's client got or showed them
+    fn acknowledge(&mut self, ids: &[String], state: DeliveryState) {
+        for message in self.messages.iter_mut()
+            .filter(|message| message.sender.ends_with("(self)") && ids.contains(&message.id)) {
+            message.advance(state);
+        }
+    }
+
+    //whether message was received in the selected conversation and not shown there before
+    fn unread(&self, message: &Message) -> bool {
+        !message.read && !message.sender.ends_with("(self)") && self.conversation_of(message) == selected_node()
+    }
+
+    //marks the messages in the selected conversation read, as it is on screen, and returns the ids
+    //of the ones that have one, and of those the ones that came in an envelope and can be answered with a receipt
+    fn mark_read(&mut self) -> (Vec<String>, Vec<String>) {
+        let unread: Vec<usize> = (0..self.messages.len())
+            .filter(|index| self.unread(&self.messages[*index]))
+            .collect();
+        let mut ids = Vec::new();
+        let mut receipts = Vec::new();
+        for index in unread {
+            let message = &mut self.messages[index];
+            message.read = true;
+            if message.id.is_empty() {
+                continue;
+            }
+            let format = message.metadata.as_ref().and_then(|metadata| metadata.format);
+            if format == Some(Format::Envelope) {
+                receipts.push(message.id.clone());
+            }
+            ids.push(message.id.clone());
+        }
+        (ids, receipts)
+    }
+
+    //puts the newer copy of a message of ours in place of the old one
+    fn replace(&mut self, updated: Message) {
+        if let Some(message) = self.messages.iter_mut().find(|message| message.id == updated.id) {
+            *message = updated;
+        }
+    }
+
+    //adds a received message to the log and notifies unless its conversation is selected
+    fn receive(&mut self, message: Message) {
+        let conversation = self.conversation_of(&message);
+        if !self.notifications.contains(&conversation) && !selected_node().eq(&conversation){
+            self.notifications.push(conversation)
+        }
+        self.messages.push(message);
+    }
+
+    //moves the peer selection one up or down, wrapping around at both ends
+    fn move_selection(&mut self, up: bool) {
+        if let Some(selected) = self.peer_list_state.selected() {
+            if !self.seen_nodes.is_empty(){
+                let peers_num = self.seen_nodes.len();
+                if up {
+                    if selected == 0 {
+                        self.peer_list_state.select(Some(peers_num - 1));
+                    } else {
+                        self.peer_list_state.select(Some(selected - 1));
+                    }
+                } else if selected >= peers_num - 1 {
+                    self.peer_list_state.select(Some(0));
+                } else {
+                    self.peer_list_state.select(Some(selected + 1));
+                }
+                self.sync_selection();
+            }
+        }
+    }
+
+    //the messages of the selected conversation, mine included, oldest first
+    fn conversation(&self) -> Vec<&Message> {
+        let selected = selected_node();
+        self.messages.iter()
+            .filter(|message| self.conversation_of(message) == selected)
+            .collect()
+    }
+
+    //the message picked in the chat log when reading
+    fn picked_message(&self) -> Option<&Message> {
+        self.conversation().get(self.message_selected).copied()
+    }
+
+    //who wrote the message with this id and how it starts, to show above the replies to it.
+    //replies may overtake the message they answer on the way, it is looked up again on every draw
+    fn quote(&self, id: &str) -> String {
+        let Some(message) = self.messages.iter().find(|message| message.id == id) else {
+            return String::from("a message that has not arrived yet");
+        };
+        let sender = if message.sender.ends_with("(self)") {
+            String::from("You")
+        } else {
+            message.metadata.as_ref().and_then(|metadata| metadata.display_name.clone())
+                .unwrap_or_else(|| message.sender.clone())
+        };
+        let first_line = message.message.lines().next().unwrap_or_default();
+        let mut snippet = String::new();
+        for c in first_line.chars() {
+            if snippet.width() >= QUOTE_WIDTH {
+                snippet.push('\u{2026}');
+                break;
+            }
+            snippet.push(c);
+        }
+        format!("{}: {}", sender, snippet)
+    }
+
+    //moves the picked message one up or down, stopping at both ends
+    fn move_message_selection(&mut self, up: bool) {
+        let count = self.conversation().len();
+        self.message_selected = if up {
+            self.message_selected.saturating_sub(1)
+        } else {
+            (self.message_selected + 1).min(count.saturating_sub(1))
+        };
+    }
+
+    //keeps track of a fragmented bundle, it is done with once all of it is there
+    fn update_partial(&mut self, progress: fragments::Progress) {
+        self.partials.retain(|partial| partial.id != progress.id);
+        if progress.received < progress.total {
+            self.partials.push(progress);
+        }
+    }
+
+    //takes a fresh read of the daemon's store, keeping the selection where it was if it still fits
+    fn update_store(&mut self, stored: Vec<StoredBundle>) {
+        let selected = self.store_state.selected().unwrap_or(0);
+        self.store_state.select((!stored.is_empty()).then(|| selected.min(stored.len() - 1)));
+        self.stored = stored;
+    }
+
+    //moves the selection in the store browser one up or down, stopping at both ends
+    fn move_store_selection(&mut self, up: bool) {
+        if let Some(selected) = self.store_state.selected() {
+            let next = if up { selected.saturating_sub(1) } else { (selected + 1).min(self.stored.len() - 1) };
+            self.store_state.select(Some(next));
+        }
+    }
+}
+
+//most columns of a quoted message shown above a reply
+const QUOTE_WIDTH: usize = 40;
+
 //global variable to keep track of the current selected node
-static mut GLOBAL_SELECTED_NODE: String = String::new();
+static GLOBAL_SELECTED_NODE: Mutex<String> = Mutex::new(String::new());
 //static mut GLOBAL_LOCAL_USER: String = String::new();
 
+//returns a copy of the current selected node
+fn selected_node() -> String {
+    GLOBAL_SELECTED_NODE.lock().unwrap().clone()
+}
+
 fn main() -> result::Result<(), Box<dyn Error>> {
-    //check if chat.json exists
-    let chatfile = "chat.json";
-    let chatfile_exists = std::path::Path::new(chatfile).exists();
-    if !chatfile_exists {
-        // create chat.json if doesnt exist
-        let mut file = File::create(chatfile)?;
-        //initializes chat.json with an empty array
-        file.write_all(b"[]\n")?;
+    //--data-dir <dir> keeps chat.json, config.json and the other files somewhere else than here
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(position) = args.iter().position(|arg| arg == "--data-dir") {
+        let dir = args.get(position + 1).ok_or("--data-dir needs a directory")?;
+        config::set_data_dir(dir.into())?;
     }
+    write::create_log()?;
+    let mut config = config::load()?;
+    //--mock runs against a made up daemon inside the client instead of dtnd
+    let mock = if args.iter().any(|arg| arg == "--mock") {
+        Some(mock::MockDaemon::new("mock")?)
+    } else {
+        None
+    };
+    //start our own dtnd if asked to, otherwise attach to the one already running
+    let mut dtnd = if config.dtnd.managed && mock.is_none() {
+        Some(processes::dtndstart(&config.dtnd, &endpoints(&config))?)
+    } else {
+        None
+    };
+    let app = Application {
+        //load the chat log once, from here on it is kept up to date in memory
+        messages: write::read_messages()?,
+        quarantined: quarantine::count()?,
+        partials: fragments::waiting()?,
+        endpoint: config.endpoint.clone(),
+        //channels and rooms are listed above the peers, which show up as they are discovered
+        seen_nodes: config.channels.iter()
+            .map(|channel| SeenNode::new(format!("#{}", channel), State::Channel))
+            .chain(config.rooms.iter().map(|room| SeenNode::new(format!("~{}", room), State::Room)))
+            .collect(),
+        ..Default::default()
+    };
     // Starts creating tui
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
-    let app = Application::default();
     // calls run and loop until q is pressed
-    let res = run(&mut terminal, app); 
+    let res = if let Some(daemon) = mock {
+        mock::simulate(daemon.clone(), config.endpoint.clone());
+        run(&mut terminal, app, daemon, &mut config, &mut dtnd)
+    } else {
+        //talks to the configured dtnd over its REST and websocket interfaces
+        let client = DtndClient::with_host_and_port(config.dtnd.host.clone(), config.dtnd.port);
+        run(&mut terminal, app, client, &mut config, &mut dtnd)
+    };
 
     // to restore the terminal after quitting
     disable_raw_mode()?;
@@ -115,29 +471,35 @@ fn main() -> result::Result<(), Box<dyn Error>> {
     }
     print!("\x1B[2J\x1B[1;1H");
     println!("Terminal restored." );
-    let dtnds = processes::dtndfind();
-    for dtnd in dtnds{
-        processes::dtndkill(dtnd);
-        thread::sleep(Duration::from_secs(1));
+    //only stop the dtnd we started ourselves
+    if let Some(dtnd) = dtnd.as_mut() {
+        println!("Stopping dtnd with PID: {}", dtnd.id());
+        processes::dtndstop(dtnd)?;
+        println!("\u{2714}  Daemon shutdown successful. Thank you for using DTN Chat Client." );
+    } else {
+        println!("\u{2714}  Left dtnd running. Thank you for using DTN Chat Client." );
     }
-    println!("\u{2714}  Daemon shutdown successful. Thank you for using DTN Chat Client." );
     Ok(())
 }
 
-fn run<B: Backend>(terminal: &mut Terminal<B>, mut app: Application) -> io::Result<()> {
-    let mut peer_list_state = ListState::default(); // create a ListState to track selected node
-    peer_list_state.select(Some(0)); //select Some() initial value for ListState
-    let mut peernames: Vec<_> = Vec::new() ;
-    let mut seen_nodes: Vec<SeenNode> = Vec::new() ; // create Vec<SeenNode>
-    //create HashMap(to memorize the index corresponds to the name of a peer in the peernames)
-    let mut node_map= HashMap::new(); 
-    let mut map_num: i32 = 0; // initialize value of index for node_map
-    let mut notifications: Vec<String> = Vec::new();
+//endpoints the managed dtnd registers when it starts, rooms are registered by the worker
+fn endpoints(config: &Config) -> Vec<String> {
+    std::iter::once(config.endpoint.clone())
+        .chain(config.channels.iter().cloned())
+        .collect()
+}
+
+fn run<B: Backend, T: Transport>(terminal: &mut Terminal<B>, mut app: Application, client: T,
+                                 config: &mut Config, dtnd: &mut Option<Child>) -> io::Result<()> {
     let tick = Duration::from_millis(250); // set up a tick rate for the "input reading thread"
 
-    // create channel of communication between the thread and main thread, passes read values 
+    // create channel of communication between the threads and main thread, passes read values 
     let (tx, rx) = mpsc::channel(); 
 
+    //the worker does all the talking to the daemon and reports back through the channel
+    let worker = worker::spawn(client, config.endpoint.clone(), config.name.clone(), config.channels.clone(),
+                                config.rooms.clone(), tx.clone())?;
+
     //spawn the thread that loops the reading task,executes the closure ||
     thread::spawn(move || {
         //initialize mutable last_tick
@@ -162,235 +524,312 @@ fn run<B: Backend>(terminal: &mut Terminal<B>, mut app: Application) -> io::Resu
     });
     loop {
         //draw the Frame<> in the closure with the value passed by calling ui()
-        terminal.draw(|f| ui(f, &app, &mut peer_list_state, 
-            &mut peernames,&mut seen_nodes,&mut node_map,&mut map_num, &mut notifications) )?;
-
-        // match the Key read from thread with a corresponding action
-        if let AppEvent::Input(key) = rx.recv().unwrap() {
-            match app.input_mode {
-                //different actions in different ChatModes
-                ChatMode::Idle => match key.code {
-                    KeyCode::Enter => {
-                        app.input_mode = ChatMode::Chatting;
-                    }
-                    //point peer_list_state at the correct peer when up or down is pressed
-                    KeyCode::Down => {
-                        if let Some(selected) = peer_list_state.selected() {
-                            if !peernames.is_empty(){
-                                let peers_num = peernames.len();
-                                if selected >= peers_num - 1 {
-                                    peer_list_state.select(Some(0));
-                                } else {
-                                    peer_list_state.select(Some(selected + 1));
-                                }  
+        terminal.draw(|f| ui(f, &mut app))?;
+
+        //wait for the next event and take whatever else is already queued with it,
+        //so a burst of incoming bundles costs a single redraw
+        let first = rx.recv().unwrap();
+        let queued: Vec<_> = rx.try_iter().collect();
+        for event in std::iter::once(first).chain(queued) {
+            match event {
+                AppEvent::NodeId(node_id) => app.local_user = node_id.name(),
+                AppEvent::PeerUpdate(peers) => app.update_peers(peers),
+                AppEvent::IncomingMessage(message) => app.receive(message),
+                AppEvent::SendResult(message, error) => {
+                    app.status = error.map(|err| {
+                        if message.delivery == Some(DeliveryState::Pending) {
+                            format!("Sending to {} delayed, retrying: {}", app.conversation_of(&message), err)
+                        } else {
+                            format!("Sending to {} failed: {}", app.conversation_of(&message), err)
+                        }
+                    });
+                    app.messages.push(message);
+                }
+                AppEvent::Retried(message, error) => {
+                    app.status = error
+                        .map(|err| format!("Sending to {} failed: {}", app.conversation_of(&message), err));
+                    app.replace(message);
+                }
+                AppEvent::Delivery(bundle_id, state) => app.update_delivery(&bundle_id, state),
+                AppEvent::Store(Ok(stored)) => app.update_store(stored),
+                AppEvent::Quarantined(endpoint, err) => {
+                    app.quarantined += 1;
+                    app.status = Some(format!("Unreadable bundle on {}: {}", endpoint, err));
+                }
+                AppEvent::Fragment(progress) => app.update_partial(progress),
+                AppEvent::Receipt(ids, state) => app.acknowledge(&ids, state),
+                AppEvent::Store(Err(err)) => app.status = Some(format!("Reading the bundle store failed: {}", err)),
+                AppEvent::Tick => {}
+                // match the Key read from thread with a corresponding action
+                AppEvent::Input(key) => match app.input_mode {
+                    //different actions in different ChatModes
+                    ChatMode::Idle => match key.code {
+                        KeyCode::Enter => {
+                            app.input_mode = ChatMode::Chatting;
+                        }
+                        //point peer_list_state at the correct peer when up or down is pressed
+                        KeyCode::Down => app.move_selection(false),
+                        KeyCode::Up => app.move_selection(true),
+                        //show or hide the details of the selected peer
+                        KeyCode::Char('i') => app.show_details = !app.show_details,
+                        //pick a message in the chat log, starting with the newest
+                        KeyCode::Char('m') => {
+                            app.input_mode = ChatMode::Reading;
+                            app.message_selected = app.conversation().len().saturating_sub(1);
+                        }
+                        //open the store browser, it fills in once the worker read the store
+                        KeyCode::Char('b') => {
+                            app.input_mode = ChatMode::Browsing;
+                            app.inspecting = false;
+                            let _result = worker.send(worker::Command::ListBundles);
+                        }
+                        //q to quit, goes back to main() to restore terminal.
+                        KeyCode::Char('q') => {
+                            return Ok(());
+                        }
+                        //do nothing
+                        _ => {}
+                    },
+                    ChatMode::Reading => match key.code {
+                        KeyCode::Down => app.move_message_selection(false),
+                        KeyCode::Up => app.move_message_selection(true),
+                        //show or hide the details of the picked message
+                        KeyCode::Enter => app.show_message = !app.show_message,
+                        //answer the picked message, the next one sent refers to it
+                        KeyCode::Char('r') => match app.picked_message().map(|message| message.id.clone()) {
+                            Some(id) if !id.is_empty() => {
+                                app.replying = Some(id);
+                                app.input_mode = ChatMode::Chatting;
+                                app.show_message = false;
                             }
-                            
+                            Some(_) => app.status = Some(String::from("Messages sent as plain text can't be replied to")),
+                            None => {}
+                        },
+                        KeyCode::Esc => {
+                            app.input_mode = ChatMode::Idle;
+                            app.show_message = false;
                         }
-                    }
-                    KeyCode::Up => {
-                        if let Some(selected) = peer_list_state.selected() {
-                            if !peernames.is_empty(){
-                                let peers_num = peernames.len();
-                                if selected == 0 {
-                                    peer_list_state.select(Some(peers_num - 1));
-                                } else {
-                                    peer_list_state.select(Some(selected - 1));
-                                }
+                        KeyCode::Char('q') => {
+                            return Ok(());
+                        }
+                        //do nothing
+                        _ => {}
+                    },
+                    ChatMode::Browsing => match key.code {
+                        KeyCode::Down => app.move_store_selection(false),
+                        KeyCode::Up => app.move_store_selection(true),
+                        //show or hide the payload of the selected bundle
+                        KeyCode::Enter => app.inspecting = !app.inspecting,
+                        KeyCode::Char('r') => {
+                            let _result = worker.send(worker::Command::ListBundles);
+                        }
+                        KeyCode::Esc | KeyCode::Char('b') => app.input_mode = ChatMode::Idle,
+                        KeyCode::Char('q') => {
+                            return Ok(());
+                        }
+                        //do nothing
+                        _ => {}
+                    },
+                    ChatMode::Chatting => match key.code {
+                        KeyCode::Enter => {
+                            //get all String in the Send a message box and pass it to message
+                            let message:String = app.input.drain(..).collect();
+                            //lines starting with / are commands to the client, not messages
+                            if let Some(line) = message.strip_prefix('/') {
+                                command(&mut app, config, &worker, dtnd, line);
+                            }
+                            //avoid empty messages
+                            else if !message.is_empty(){
+                                let options = config.options_for(&selected_node());
+                                send(&mut app, &worker, message, options);
                             }
                         }
-                    }
-                    //q to quit, goes back to main() to restore terminal.
-                    KeyCode::Char('q') => {
-                        return Ok(());
-                    }
-                    //do nothing
-                    _ => {}
-                },
-                ChatMode::Chatting => match key.code {
-                    KeyCode::Enter => {
-                        //get all String in the Send a message box and pass it to message
-                        let message:String = app.input.drain(..).collect();
-                        //avoid empty messages
-                        if !message.is_empty(){
-                            //add the messages Vec<>
-                            app.messages.push(message.clone());
-                            unsafe{
-                                processes::send(GLOBAL_SELECTED_NODE.clone(), message.clone());
-                                //create a special name of sender when sender is myself, to update my chat.json
-                                let self_concat = GLOBAL_SELECTED_NODE.clone() + "(self)";
-                                //write message
-                                let _result = write::write_message(&self_concat,&message);
-                            } 
+                        KeyCode::Backspace => {
+                            //backspace effect
+                            app.input.pop();
                         }
-                        
-                    }
-                    KeyCode::Backspace => {
-                        //backspace effect
-                        app.input.pop();
-                    }
-                    KeyCode::Esc => {
-                        //quit ChatMode
-                        app.input_mode = ChatMode::Idle;
-                    }
-                    KeyCode::Char(c) => {
-                        //get character typed
-                        app.input.push(c);
-                    }
-                    //do nothing
-                    _ => {}
+                        KeyCode::Esc => {
+                            //quit ChatMode, a reply not sent yet is given up
+                            app.input_mode = ChatMode::Idle;
+                            app.replying = None;
+                        }
+                        KeyCode::Char(c) => {
+                            //get character typed
+                            app.input.push(c);
+                        }
+                        //do nothing
+                        _ => {}
+                    },
                 },
             }
         }
+        read_conversation(&mut app, config, &worker);
+    }
+}
+
+//marks what arrived in the selected conversation read and has the worker do the same in chat.json
+//and tell a peer about it, unless they are sent plain text or asked for no receipts.
+//the store browser hides the conversation, so nothing is read while it is open
+fn read_conversation(app: &mut Application, config: &Config, worker: &mpsc::Sender<worker::Command>) {
+    if matches!(app.input_mode, ChatMode::Browsing) || !app.messages.iter().any(|message| app.unread(message)) {
+        return;
+    }
+    let selected = selected_node();
+    let (ids, receipts) = app.mark_read();
+    let _result = worker.send(worker::Command::MarkRead { conversation: selected.clone(), ids });
+    let options = config.options_for(&selected);
+    let peer = matches!(app.selected().map(|node| &node.state), Some(State::Near | State::Away));
+    if peer && !receipts.is_empty() && options.receipts && !options.plain {
+        let _result = worker.send(worker::Command::Receipt { peer: selected, ids: receipts, options });
     }
 }
-//passes bunch of initialized values from runapp()
-fn ui<B: Backend>(f: &mut Frame<B>, 
-                app: &Application, 
-                peer_list_state: &mut ListState, 
-                peernames: &mut Vec<ListItem>,
-                seen_nodes: &mut Vec<SeenNode>,
-                node_map: &mut HashMap<i32, String>,
-                map_num: &mut i32,
-                notifications: &mut Vec<String>){
-    //calls peers() to get output from dtnquery to check neighbours
-    let json_string = processes::peers();
-    //skips "Listing of peers"
-    let json_string = &json_string.lines().skip(1).collect::<Vec<&str>>().join("\n");
-    //maps json values deserialized by serde_json
-    let nodes: Map<String, Value> = serde_json::from_str(json_string).unwrap();
-    //for every mapped value, push the name if its new
-    for (name, _nodeinfo) in nodes.iter() {
-        let new_name = ListItem::new(name.clone().to_string());
-            if !peernames.contains(&new_name) &&
-            !peernames.contains(&ListItem::new("\u{1F4E8} ".to_string() + &name.clone().to_string())){
-                peernames.push(new_name.clone());
-                //push to seen_nodes too to keep track of states
-                let new_seen_node = SeenNode::new
-                        (name.to_string(), State::Near);
-                seen_nodes.push(new_seen_node);
-            
-                //update node_map and increment map_num
-                node_map.insert(map_num.to_owned(),name.clone().to_string());
-                *map_num += 1;
-                
+
+//carries out a line typed with a leading /, problems are shown in the header
+fn command(app: &mut Application, config: &mut Config, worker: &mpsc::Sender<worker::Command>,
+           dtnd: &mut Option<Child>, line: &str) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let selected = selected_node();
+    app.status = None;
+    match words.as_slice() {
+        ["join", room] if rooms::valid_name(room) => {
+            if app.add_room(room) {
+                config.rooms.push(room.to_string());
+                let _result = worker.send(worker::Command::JoinRoom(room.to_string()));
+                save(app, config);
             }
         }
-    //add notification icon if notification list contain the name.
-    for (_, name) in node_map.iter() {
-        if notifications.contains(&name.clone()) && 
-            unsafe{!GLOBAL_SELECTED_NODE.eq(&name.clone())} &&
-            !peernames.contains(&ListItem::new("\u{1F4E8} ".to_string() + &name.clone())){
-            if let Some(index) = peernames.iter().position(|x| x == &ListItem::new(name.clone())) {
-                let namewithnoti = "\u{1F4E8} ".to_string() + &name.clone();
-                peernames[index] = ListItem::new(namewithnoti);
+        ["join", ..] => app.status = Some(String::from("Room names are letters, digits, - and _")),
+        ["leave"] => match app.remove_selected_room() {
+            Some(room) => {
+                config.rooms.retain(|joined| joined != &room);
+                config.conversations.remove(&format!("~{}", room));
+                let _result = worker.send(worker::Command::LeaveRoom(room));
+                save(app, config);
             }
-            //remove notification icon when selected a name with notification
-        }else if unsafe{GLOBAL_SELECTED_NODE.eq(&name.clone())}{
-            if let Some(index) = peernames.iter()
-                .position(|x| x == &ListItem::new("\u{1F4E8} ".to_string() + &name.clone())) {
-                peernames[index] = ListItem::new(name.clone());
-                notifications.retain(|r| r != &name.clone());
+            None => app.status = Some(String::from("Select the room to leave")),
+        },
+        //options for every message to the selected conversation from now on
+        ["set", options @ ..] if !selected.is_empty() => {
+            let mut conversation = config.options_for(&selected);
+            let applied = conversation.apply(options);
+            if applied < options.len() {
+                app.status = Some(format!("Unknown option {}", options[applied]));
+            } else {
+                config.conversations.insert(selected, conversation);
+                save(app, config);
             }
         }
-    }
-    //update states list by matching with node_map, Near if matched, Away if otherwise
-    let mut states: Vec<_> = Vec::new() ;
-    if !seen_nodes.is_empty(){
-        for seen_node in seen_nodes{
-            let mut found = false;
-            for (name, _nodeinfo) in nodes.iter() {
-                if seen_node.name == *name{
-                    found = true
-                }
+        //back to the options in send
+        ["reset"] => {
+            if config.conversations.remove(&selected).is_some() {
+                save(app, config);
             }
-            if !found{
-                seen_node.state = State::Away;
-            } else{
-                seen_node.state = State::Near;
+        }
+        //options for just this message, the text starts at the first word that is not an option
+        ["send", rest @ ..] if !selected.is_empty() => {
+            let mut options = config.options_for(&selected);
+            let applied = options.apply(rest);
+            if applied == rest.len() {
+                app.status = Some(String::from("Nothing to send after the options"));
+            } else {
+                send(app, worker, rest[applied..].join(" "), options);
             }
-            let state : String = seen_node.state.to_string();
-            let new_state = ListItem::new(state)
-                .style(match seen_node.state {
-                    State::Near => Style::default().fg(Color::Green),
-                    State::Away => Style::default().fg(Color::Red),
-                });
-            states.push(new_state);
-            
         }
-
-    }
-    
-    //update GLOBAL_SELECTED_NODE    
-    if !node_map.is_empty(){
-        let to_find = peer_list_state.selected().unwrap() as i32;
-        if let Some(string) = node_map.get(&to_find) {
-            unsafe {
-                GLOBAL_SELECTED_NODE = string.to_owned(); 
+        //a peer dtnd cant find by discovery, replacing the one with the same node name
+        ["peer", "add", cla, address, port, node] => match StaticPeer::new(cla, address, port, node) {
+            Ok(peer) => {
+                config.dtnd.static_peers.retain(|known| known.node != peer.node);
+                let url = peer.url();
+                config.dtnd.static_peers.push(peer);
+                save(app, config);
+                apply_static_peers(app, config, dtnd, &format!("restart dtnd with -s {} to add it", url));
+            }
+            Err(err) => app.status = Some(err),
+        },
+        ["peer", "add", ..] => app.status = Some(String::from("Use /peer add <cla> <address> <port> <node>")),
+        ["peer", "remove", node] => {
+            match config.dtnd.static_peers.iter().position(|peer| peer.node == *node) {
+                Some(position) => {
+                    let peer = config.dtnd.static_peers.remove(position);
+                    save(app, config);
+                    apply_static_peers(app, config, dtnd, &format!("restart dtnd without -s {} to remove it", peer.url()));
+                }
+                None => app.status = Some(format!("{} is not a static peer", node)),
             }
-        } else {
-            println!("Error finding the key.");
         }
-        
+        _ => app.status = Some(format!("Unknown command /{}", line)),
     }
+}
 
-    //check for new message using receive()
-    let new_message = processes::receive();
-    //if theres a message
-    if !new_message.is_empty(){
-        //get the sender's name and the message
-        let name_and_message = decode::extract_source_message(new_message);
-        //updates chat log chat.json
-        let _result = write::write_message(&name_and_message[0],&name_and_message[1]);
-        let name = &name_and_message[0];
-        if !notifications.contains(name) && unsafe{!GLOBAL_SELECTED_NODE.eq(name)}{
-            notifications.push(name.to_string())
-        }
+//restarts the managed dtnd so it picks up the changed static peers,
+//a dtnd we dont manage has to be restarted by hand, which hint tells how.
+//a dtnd keeping its bundles in memory would lose the ones still waiting for a peer, so it is left running
+fn apply_static_peers(app: &mut Application, config: &Config, dtnd: &mut Option<Child>, hint: &str) {
+    if app.status.is_some() {
+        return;
     }
-    
-    //Gathering chat log data
-    // Read the contents of the file into a string: contents to display later in ListItems
-    let mut file = match File::open("chat.json") {
-        Ok(file) => file,
-        Err(e) => panic!("Unable to open file: {:?}", e),
+    app.status = match dtnd.as_mut() {
+        Some(_dtnd) if config.dtnd.db == "mem" => {
+            Some(format!("Saved, dtnd keeps bundles in memory and would lose them, {}", hint))
+        }
+        Some(dtnd) => processes::dtndrestart(dtnd, &config.dtnd, &endpoints(config)).err()
+            .map(|err| format!("Restarting dtnd failed: {}", err)),
+        None => Some(format!("Saved, {}", hint)),
     };
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-
-    //deserialize the json string into a Vec<Message> 
-    let messages: Vec<Message> = serde_json::from_str(&contents).unwrap_or(Vec::new());
-
-    //create the ListItems to be displayed, iterates every message in chat.json, 
-    //filters only the messages from selected node and my mesages to the selected node
-    let message_list: Vec<ListItem> = messages.iter()
-        .filter(|message| {
-            unsafe{
-                let sender = &message.sender;
-                sender == &GLOBAL_SELECTED_NODE.clone() || sender == &(GLOBAL_SELECTED_NODE.clone() + "(self)") 
-            }   
-        })
-        .map(|message| {
-            let sender = &message.sender;
-            unsafe{
-                if sender == &(GLOBAL_SELECTED_NODE.clone() + "(self)"){
-                    let msg = vec![
-                    Span::styled("You", Style::default()
-                        .add_modifier(Modifier::BOLD)),
-                    Span::raw(" : "),
-                    Span::raw( &message.message)];
-                    let text = Text::from(Spans::from(msg));
-                    ListItem::new(text)
-                } else {
-                    let msg = vec![
-                    Span::styled(&message.sender, Style::default()
-                        .add_modifier(Modifier::BOLD)),
-                    Span::raw(" : "),
-                    Span::raw( &message.message)];
-                    let text = Text::from(Spans::from(msg));
-                    ListItem::new(text)
-                } 
+}
+
+//writes config.json, a failure is shown in the header
+fn save(app: &mut Application, config: &Config) {
+    app.status = config::save(config).err()
+        .map(|err| format!("Saving config.json failed: {}", err));
+}
+
+//hands message for the selected peer, channel or room to the worker, as a reply if one was picked
+fn send(app: &mut Application, worker: &mpsc::Sender<worker::Command>, message: String, options: SendOptions) {
+    let selected = selected_node();
+    let reply_to = app.replying.take();
+    //channels are named #(endpoint) and rooms ~(room) in the peer list,
+    //the message shows up in the log once the worker handed it to the daemon
+    let command = match app.selected().map(|node| &node.state) {
+        Some(State::Channel) => {
+            let channel = selected.trim_start_matches('#').to_string();
+            worker::Command::SendChannel { channel, message, reply_to, options }
+        }
+        Some(State::Room) => {
+            let room = selected.trim_start_matches('~').to_string();
+            worker::Command::SendRoom { room, message, reply_to, options }
+        }
+        _ => worker::Command::Send { peer: selected, message, reply_to, options },
+    };
+    let _result = worker.send(command);
+}
+
+//renders the current state of the application, all the data is gathered by the worker
+fn ui<B: Backend>(f: &mut Frame<B>, app: &mut Application){
+    let selected = selected_node();
+    //list of seen peers, with the notification icon if they sent something unread
+    let peernames: Vec<ListItem> = app.seen_nodes.iter()
+        .map(|node| {
+            if app.notifications.contains(&node.name) && node.name != selected {
+                ListItem::new("\u{1F4E8} ".to_string() + &node.name)
+            } else {
+                ListItem::new(node.name.clone())
             }
         })
-        .collect(); 
+        .collect();
+    //states of seen peers, Near in green and Away in red
+    let states: Vec<ListItem> = app.seen_nodes.iter()
+        .map(|node| {
+            ListItem::new(node.state.to_string())
+                .style(match node.state {
+                    State::Near => Style::default().fg(Color::Green),
+                    State::Away => Style::default().fg(Color::Red),
+                    State::Channel => Style::default().fg(Color::Cyan),
+                    State::Room => Style::default().fg(Color::Magenta),
+                })
+        })
+        .collect();
+
     //==========everything ready to be put in widgets at this point==========
 
     let biggerchunks = Layout::default()
@@ -443,7 +882,7 @@ fn ui<B: Backend>(f: &mut Frame<B>,
     f.render_widget(bigblock, f.size());
     
     //display list of seen peers
-    let list = List::new(peernames.to_owned())
+    let list = List::new(peernames)
             .block(Block::default()
             .borders(Borders::ALL)
             .title("Seen Peers")
@@ -454,10 +893,10 @@ fn ui<B: Backend>(f: &mut Frame<B>,
         .bg(Color::DarkGray)
         .fg(Color::White)
         .add_modifier(Modifier::BOLD),);
-    f.render_stateful_widget(list, bigchunks[0],peer_list_state);
+    f.render_stateful_widget(list, bigchunks[0],&mut app.peer_list_state);
 
     //display list of States
-    let statelist = List::new(states.to_owned())
+    let statelist = List::new(states)
             .block(Block::default()
             .borders(Borders::ALL)
             .title("State")
@@ -474,7 +913,7 @@ fn ui<B: Backend>(f: &mut Frame<B>,
     //get Vec<Span> and style to be rendered
     let (msg, style) = match app.input_mode {
         ChatMode::Idle => 
-            if peernames.is_empty(){
+            if app.seen_nodes.is_empty(){
                 (vec![
                 Span::raw("Press "),
                 Span::styled("q", Style::default()
@@ -492,7 +931,19 @@ fn ui<B: Backend>(f: &mut Frame<B>,
                 Span::styled("Enter", Style::default()
                     .add_modifier(Modifier::BOLD)
                     .add_modifier(Modifier::ITALIC)),
-                Span::raw(" to send a message"),
+                Span::raw(" to send a message, "),
+                Span::styled("i", Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::ITALIC)),
+                Span::raw(" for peer details, "),
+                Span::styled("m", Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::ITALIC)),
+                Span::raw(" to pick a message, "),
+                Span::styled("b", Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::ITALIC)),
+                Span::raw(" for the bundle store"),
             ],
             Style::default().add_modifier(Modifier::RAPID_BLINK))
             }
@@ -511,6 +962,42 @@ fn ui<B: Backend>(f: &mut Frame<B>,
             ],
             Style::default(),
         ),
+        ChatMode::Reading => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("Esc", Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::ITALIC)),
+                Span::raw(" to go back, "),
+                Span::styled("Enter", Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::ITALIC)),
+                Span::raw(" for message details, "),
+                Span::styled("r", Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::ITALIC)),
+                Span::raw(" to reply"),
+            ],
+            Style::default(),
+        ),
+        ChatMode::Browsing => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("Esc", Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::ITALIC)),
+                Span::raw(" to go back, "),
+                Span::styled("Enter", Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::ITALIC)),
+                Span::raw(" to show the payload, "),
+                Span::styled("r", Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .add_modifier(Modifier::ITALIC)),
+                Span::raw(" to refresh"),
+            ],
+            Style::default(),
+        ),
     };
 
     
@@ -524,46 +1011,110 @@ fn ui<B: Backend>(f: &mut Frame<B>,
     let prompt = Paragraph::new(text).alignment(Alignment::Right);
     f.render_widget(prompt, chunks[1]);
 
-    let current_user = Paragraph::new(format!("Current user: {}", processes::hostname()))
+    //the node id only shows up once the worker reached the daemon
+    let local_user = if app.local_user.is_empty() { "(waiting for dtnd)" } else { app.local_user.as_str() };
+    //bundles that could not be read are counted next to the user
+    let quarantined = match app.quarantined {
+        0 => String::new(),
+        1 => String::from("   1 unreadable bundle in quarantine.json"),
+        count => format!("   {} unreadable bundles in quarantine.json", count),
+    };
+    let current_user = Paragraph::new(format!("Current user: {}{}", local_user, quarantined))
         .alignment(Alignment::Left).style(Style::default().add_modifier(Modifier::BOLD));
     f.render_widget(current_user, biggerchunks[0]);
 
-    
-    let mut chat_list_state = ListState::default(); // create a ListState to track selected node
-    
-    if !message_list.is_empty(){
-        let messages_num = message_list.len();
-        chat_list_state.select(Some(messages_num - 1)); //select Some() initial value for ListState
+    //last problem the worker ran into, if any
+    if let Some(status) = &app.status {
+        let status = Paragraph::new(status.as_str())
+            .alignment(Alignment::Right).style(Style::default().fg(Color::Red));
+        f.render_widget(status, biggerchunks[0]);
     }
-    
-    
-    //display the list of messages
-    let messages = List::new(message_list)
-        .block(Block::default()
-        .borders(Borders::ALL)
-        .title("Chat log")
-        .title_alignment(Alignment::Center));
-    f.render_stateful_widget(messages, chunks[2],&mut chat_list_state);
 
-    //send a message box
+    //the details of the selected peer take the top of the chat log when shown
+    let chat_area = if app.show_details {
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(9), Constraint::Min(1)].as_ref())
+            .split(chunks[2]);
+        let details = Paragraph::new(peer_details(app, &selected))
+            .block(Block::default()
+            .borders(Borders::ALL)
+            .title("Peer details")
+            .title_alignment(Alignment::Center));
+        f.render_widget(details, areas[0]);
+        areas[1]
+    } else {
+        chunks[2]
+    };
+
+    //the store browser takes the place of the chat log while it is open
+    if let ChatMode::Browsing = app.input_mode {
+        store_browser(f, app, chat_area);
+    } else {
+        //the messages of the selected conversation, wrapped to fit inside the borders
+        let message_list = message_items(app, chat_area.width.saturating_sub(2) as usize);
+        let mut chat_list_state = ListState::default(); // create a ListState to track selected node
+        let reading = matches!(app.input_mode, ChatMode::Reading);
+        if reading {
+            chat_list_state.select(Some(app.message_selected.min(message_list.len().saturating_sub(1))));
+        } else if !message_list.is_empty(){
+            let messages_num = message_list.len();
+            chat_list_state.select(Some(messages_num - 1)); //select Some() initial value for ListState
+        }
+        //display the list of messages, the picked one highlighted when reading
+        let messages = List::new(message_list)
+            .block(Block::default()
+            .borders(Borders::ALL)
+            .title("Chat log")
+            .title_alignment(Alignment::Center))
+            .highlight_style(if reading { Style::default().bg(Color::DarkGray) } else { Style::default() });
+        f.render_stateful_widget(messages, chat_area,&mut chat_list_state);
+        //the details of the picked message float over the chat log
+        if let Some(message) = app.picked_message().filter(|_| reading && app.show_message) {
+            let area = tui::layout::Rect {
+                x: chat_area.x + 2,
+                y: chat_area.y + 1,
+                width: chat_area.width.saturating_sub(4),
+                height: chat_area.height.saturating_sub(2).min(14),
+            };
+            let details = Paragraph::new(message_details(message))
+                .wrap(tui::widgets::Wrap { trim: false })
+                .block(Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title("Message details")
+                .title_alignment(Alignment::Center));
+            f.render_widget(tui::widgets::Clear, area);
+            f.render_widget(details, area);
+        }
+    }
+
+    //send a message box, naming the message being answered
+    //long messages scroll to the left so the end being typed stays in view
+    let title = match &app.replying {
+        Some(id) => format!("Reply to {}", app.quote(id)),
+        None => String::from("Send a message"),
+    };
+    let scrolled = app.input.width().saturating_sub(chunks[3].width.saturating_sub(3) as usize) as u16;
     let input = Paragraph::new(app.input.as_ref())
+        .scroll((0, scrolled))
         .style(match app.input_mode {
-            ChatMode::Idle => Style::default(),
+            ChatMode::Idle | ChatMode::Browsing | ChatMode::Reading => Style::default(),
             ChatMode::Chatting => Style::default().fg(Color::Yellow),
         })
         .block(Block::default()
         .borders(Borders::ALL)
-        .title("Send a message")
+        .title(title)
         .title_alignment(Alignment::Center));
     f.render_widget(input, chunks[3]);
 
     match app.input_mode {
-        ChatMode::Idle =>
+        ChatMode::Idle | ChatMode::Browsing | ChatMode::Reading =>
             {} // to hide the keyboard cursor
         ChatMode::Chatting => {
             //set cursor at the right place when chatting
             f.set_cursor(
-                chunks[3].x + app.input.width() as u16 + 1,
+                chunks[3].x + app.input.width() as u16 - scrolled + 1,
                 chunks[3].y + 1,
             )
         }
@@ -571,3 +1122,309 @@ fn ui<B: Backend>(f: &mut Frame<B>,
 
 }
 
+//what the daemon told about the peer listed as name, one field per line
+fn peer_details<'a>(app: &Application, name: &str) -> Text<'a> {
+    let peer = match app.peers.get(name) {
+        Some(peer) => peer,
+        None => return Text::raw(format!("The daemon never listed {} as a peer", name)),
+    };
+    let field = |label: &str, value: String| Spans::from(vec![
+        Span::styled(format!("{:<14}", label), Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(value),
+    ]);
+    //convergence layers with the port they listen on, if they told
+    let clas: Vec<String> = peer.cla_list.iter()
+        .map(|(cla, port)| match port {
+            Some(port) => format!("{} on port {}", cla, port),
+            None => cla.clone(),
+        })
+        .collect();
+    let mut services: Vec<String> = peer.services.iter()
+        .map(|(tag, service)| format!("{}: {}", tag, service))
+        .collect();
+    services.sort();
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
+    let last_contact = match now.checked_sub(peer.last_contact) {
+        Some(ago) => format!("{}s ago", ago),
+        None => String::from("just now"),
+    };
+    let kind = match peer.con_type {
+        PeerType::Static => "static",
+        PeerType::Dynamic => "dynamic, found by discovery",
+    };
+    Text::from(vec![
+        field("Endpoint id", peer.eid.to_string()),
+        field("Address", peer.addr.to_string()),
+        field("CLAs", if clas.is_empty() { String::from("none") } else { clas.join(", ") }),
+        field("Type", kind.to_string()),
+        field("Last contact", last_contact),
+        field("Services", if services.is_empty() { String::from("none") } else { services.join(", ") }),
+        field("Failed sends", peer.fails.to_string()),
+    ])
+}
+
+//what is known about a message, one field per line
+fn message_details<'a>(message: &Message) -> Text<'a> {
+    let field = |name: &str, value: String| Spans::from(vec![
+        Span::styled(format!("{:<14}", name), Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(value),
+    ]);
+    let mine = message.sender.ends_with("(self)");
+    let mut lines = vec![
+        field("From", if mine { String::from("You") } else { message.sender.clone() }),
+        field("Endpoint", message.endpoint.clone()),
+    ];
+    if !message.id.is_empty() {
+        lines.push(field("Id", message.id.clone()));
+    }
+    if let Some(id) = &message.reply_to {
+        lines.push(field("Reply to", id.clone()));
+    }
+    if let Some(metadata) = &message.metadata {
+        if let Some(name) = &metadata.display_name {
+            lines.push(field("Name", name.clone()));
+        }
+        if let Some(sent) = metadata.sent {
+            lines.push(field("Sent", utc(sent / 1000)));
+        }
+        lines.push(field("Created", metadata.created_unix().map_or(String::from("unknown, the sender has no clock"), utc)));
+        lines.push(field("Received", utc(metadata.received)));
+        lines.push(field("Sequence", metadata.sequence.to_string()));
+        lines.push(field("Lifetime", options::format_duration(metadata.lifetime)));
+        lines.push(field("Integrity", metadata.integrity.to_string()));
+        if let Some(format) = metadata.format {
+            lines.push(field("Format", format.to_string()));
+        }
+        if let (Some(hops), Some(limit)) = (metadata.hops, metadata.hop_limit) {
+            lines.push(field("Hops", format!("{} of at most {}", hops, limit)));
+        }
+        if let Some(previous_node) = &metadata.previous_node {
+            lines.push(field("Previous node", previous_node.clone()));
+        }
+    }
+    if mine {
+        if let Some(options) = &message.options {
+            lines.push(field("Sent with", options.to_string()));
+        }
+        if let Some(delivery) = message.delivery {
+            lines.push(field("Delivery", delivery.to_string()));
+        }
+        if !message.bundle_ids.is_empty() {
+            lines.push(field("Bundles", message.bundle_ids.join(", ")));
+        }
+    }
+    Text::from(lines)
+}
+
+//unix time as a utc date and time, 2024-05-01 13:45:12 UTC
+fn utc(unix: u64) -> String {
+    let (days, seconds) = (unix / 86400, unix % 86400);
+    //days since 1970 to a civil date, from howard hinnant's date algorithms
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, seconds / 3600, seconds % 3600 / 60, seconds % 60)
+}
+
+//creates the ListItems to be displayed, iterates every message in the chat log,
+//filters only the messages of the selected peer or channel, mine included
+fn message_items(app: &Application, width: usize) -> Vec<ListItem<'_>> {
+    app.conversation().into_iter()
+        .map(|message| {
+            let (sender, mut tail) = if message.sender.ends_with("(self)") {
+                let mut tail = Vec::new();
+                //how far the message got, then what it was sent with, dimmed behind the text
+                if let Some(delivery) = message.delivery {
+                    tail.push(Span::styled(format!("  {}", delivery), Style::default().fg(match delivery {
+                        DeliveryState::Pending | DeliveryState::Queued => Color::Yellow,
+                        DeliveryState::Forwarded => Color::Cyan,
+                        DeliveryState::Delivered => Color::Green,
+                        DeliveryState::Read => Color::LightGreen,
+                        DeliveryState::Failed | DeliveryState::Deleted | DeliveryState::Expired => Color::Red,
+                    })));
+                }
+                if let Some(options) = &message.options {
+                    tail.push(Span::styled(format!("  [{}]", options), Style::default().fg(Color::DarkGray)));
+                }
+                (String::from("You"), tail)
+            } else {
+                //a message whose bundle failed its crc check may not read as it was sent
+                let corrupted = message.metadata.as_ref().and_then(|metadata| metadata.integrity.error());
+                let tail = corrupted.into_iter()
+                    .map(|err| Span::styled(format!("  \u{26a0} {}, may be corrupted", err), Style::default().fg(Color::Red)))
+                    .collect();
+                //the name a sender picked can be anything, so their node name stays next to it
+                let sender = match message.metadata.as_ref().and_then(|metadata| metadata.display_name.as_ref()) {
+                    Some(name) if name != &message.sender => format!("{} ({})", name, message.sender),
+                    _ => message.sender.clone(),
+                };
+                (sender, tail)
+            };
+            //binary payloads are only described, which is set apart from what the sender wrote
+            let binary = message.metadata.as_ref().is_some_and(|metadata| metadata.format == Some(Format::Binary));
+            let style = if binary {
+                Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC)
+            } else {
+                Style::default()
+            };
+            //lines after the first start below the text of the first one
+            let indent = sender.width() + 3;
+            let mut lines: Vec<Spans> = wrap(&message.message, width.saturating_sub(indent))
+                .into_iter()
+                .enumerate()
+                .map(|(number, line)| if number == 0 {
+                    Spans::from(vec![
+                        Span::styled(sender.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(" : "),
+                        Span::styled(line, style),
+                    ])
+                } else {
+                    Spans::from(vec![Span::raw(" ".repeat(indent)), Span::styled(line, style)])
+                })
+                .collect();
+            if let Some(last) = lines.last_mut() {
+                last.0.append(&mut tail);
+            }
+            //a reply starts with what it answers, lined up with its text
+            if let Some(id) = &message.reply_to {
+                lines.insert(0, Spans::from(vec![
+                    Span::raw(" ".repeat(indent)),
+                    Span::styled(format!("\u{21aa} {}", app.quote(id)), Style::default().fg(Color::DarkGray)),
+                ]));
+            }
+            ListItem::new(Text::from(lines))
+        })
+        //messages still arriving in fragments come last, with how much of them is there
+        .chain(app.partials.iter()
+            .filter(|partial| {
+                let message = Message { sender: partial.sender.clone(), endpoint: partial.endpoint.clone(), ..Default::default() };
+                app.conversation_of(&message) == selected_node()
+            })
+            .map(|partial| {
+                let percent = partial.received * 100 / partial.total.max(1);
+                ListItem::new(Spans::from(vec![
+                    Span::styled(partial.sender.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(" : "),
+                    Span::styled(format!("receiving {} of {} ({}%)", store::rough_size(partial.received),
+                                         store::rough_size(partial.total), percent),
+                        Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC)),
+                ]))
+            }))
+        .collect()
+}
+
+//splits text into lines of at most width columns, at its own line breaks and at the last space
+//before a line gets too long, or anywhere in words longer than a line.
+//wide characters like cjk and emoji take two columns, other control characters show as spaces
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(2);
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        let mut used = 0;
+        for c in paragraph.chars() {
+            let c = if c.is_control() { ' ' } else { c };
+            let columns = unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+            if used + columns > width && !line.is_empty() {
+                let rest = match line.trim_end().rfind(' ') {
+                    Some(space) => line.split_off(space + 1),
+                    None => String::new(),
+                };
+                lines.push(std::mem::replace(&mut line, rest));
+                used = line.width();
+            }
+            line.push(c);
+            used += columns;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+//the bundles in the daemon's store, with the payload of the selected one below when asked for
+fn store_browser<B: Backend>(f: &mut Frame<B>, app: &mut Application, area: tui::layout::Rect) {
+    let areas = if app.inspecting {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(area)
+    } else {
+        vec![area]
+    };
+    let rows: Vec<ListItem> = app.stored.iter()
+        .map(|bundle| {
+            let age = bundle.age.map_or(String::from("?"), store::rough_duration);
+            let left = match bundle.remaining {
+                Some(remaining) if remaining >= 0 => format!("{} left", store::rough_duration(remaining as u64)),
+                Some(_) => String::from("expired"),
+                None => String::from("lifetime unknown"),
+            };
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{} \u{2192} {}", bundle.source, bundle.destination)),
+                Span::styled(format!("  {}, {} old, {}", store::rough_size(bundle.size as u64), age, left),
+                    Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    let title = format!("Bundle store ({})", app.stored.len());
+    let list = List::new(rows)
+        .block(Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_alignment(Alignment::Center))
+        .highlight_style(Style::default()
+        .bg(Color::DarkGray)
+        .add_modifier(Modifier::BOLD));
+    f.render_stateful_widget(list, areas[0], &mut app.store_state);
+    if app.inspecting {
+        let payload = match app.store_state.selected().and_then(|index| app.stored.get(index)) {
+            //text stays readable, anything else is shown as hex
+            Some(bundle) => match std::str::from_utf8(&bundle.payload) {
+                Ok(text) => format!("{}\n{}", bundle.id, text),
+                Err(_) => format!("{}\n{}", bundle.id, hex::encode(&bundle.payload)),
+            },
+            None => String::from("No bundle selected"),
+        };
+        let details = Paragraph::new(payload)
+            .wrap(tui::widgets::Wrap { trim: false })
+            .block(Block::default()
+            .borders(Borders::ALL)
+            .title("Payload")
+            .title_alignment(Alignment::Center));
+        f.render_widget(details, areas[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_breaks_at_spaces_and_inside_long_words() {
+        assert_eq!(wrap("hello world", 8), ["hello ", "world"]);
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("hi abcdefghij", 4), ["hi ", "abcd", "efgh", "ij"]);
+        assert_eq!(wrap("one\ntwo\n", 10), ["one", "two", ""]);
+        assert_eq!(wrap("", 10), [""]);
+    }
+
+    #[test]
+    fn wrap_counts_wide_characters_twice() {
+        assert_eq!(wrap("\u{65e5}\u{672c}\u{8a9e}", 4), ["\u{65e5}\u{672c}", "\u{8a9e}"]);
+        assert_eq!(wrap("\u{65e5}\u{672c}\u{8a9e}", 3), ["\u{65e5}", "\u{672c}", "\u{8a9e}"]);
+        assert_eq!(wrap("ok \u{1f600}\u{1f600}", 5), ["ok ", "\u{1f600}\u{1f600}"]);
+        assert!(wrap("\u{65e5}\u{672c}\u{8a9e} \u{30c6}\u{30ad}\u{30b9}\u{30c8}", 5).iter().all(|line| line.width() <= 5));
+    }
+
+    #[test]
+    fn wrap_shows_control_characters_as_spaces() {
+        assert_eq!(wrap("a\tb\x07c\r", 10), ["a b c "]);
+        assert_eq!(wrap("\x1b[31mred", 4), [" ", "[31m", "red"]);
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use bp7::Bundle;
+use dtn7::core::peer::{PeerAddress, PeerType};
+use dtn7::DtnPeer;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use crate::bundle;
+use crate::eid::Eid;
+use crate::envelope::{Envelope, Kind};
+use crate::options::SendOptions;
+use crate::transport::{Transport, TransportError, LISTENING_CHECK};
+use crate::write;
+
+//peers the simulation lets come and go
+const SIMULATED_PEERS: [&str; 3] = ["alice", "bob", "carol"];
+//what they say when they write
+const SIMULATED_LINES: [&str; 5] = [
+    "hello from the other side of the gap",
+    "anyone got the latest map tiles?",
+    "back in range for a few minutes",
+    "forwarding your last bundle now",
+    "\u{1f44b}",
+];
+//time between two things happening in the simulation
+const SIMULATION_STEP: Duration = Duration::from_secs(3);
+
+//a daemon that lives in the client's process, it knows the peers and bundles it is told about
+//and passes bundles to subscriptions right away. clones share the same daemon
+#[derive(Clone)]
+pub struct MockDaemon {
+    state: Arc<Mutex<MockState>>,
+}
+
+struct MockState {
+    node_id: Eid,
+    //false makes every request fail as if the daemon was gone
+    reachable: bool,
+    peers: BTreeMap<String, DtnPeer>,
+    //bundles inserted by us or received for us, by bundle id, in cbor
+    store: BTreeMap<String, Vec<u8>>,
+    registered: HashSet<String>,
+    //open subscriptions by endpoint
+    subscribers: HashMap<String, Vec<Sender<Vec<u8>>>>,
+}
+
+impl MockDaemon {
+    //a reachable daemon running as the node named node, without peers or bundles
+    pub fn new(node: &str) -> Result<MockDaemon, TransportError> {
+        let state = MockState {
+            node_id: Eid::from_name(node)?,
+            reachable: true,
+            peers: BTreeMap::new(),
+            store: BTreeMap::new(),
+            registered: HashSet::new(),
+            subscribers: HashMap::new(),
+        };
+        Ok(MockDaemon { state: Arc::new(Mutex::new(state)) })
+    }
+
+    //the state if the daemon is up, the error a client of a stopped dtnd would see otherwise
+    fn up(&self) -> Result<std::sync::MutexGuard<'_, MockState>, TransportError> {
+        let state = self.state.lock().unwrap();
+        if state.reachable {
+            Ok(state)
+        } else {
+            Err(TransportError::Io(io::Error::new(io::ErrorKind::ConnectionRefused, "mock dtnd is stopped")))
+        }
+    }
+
+    //stops or starts the daemon, stopping it breaks all subscriptions
+    pub fn set_reachable(&self, reachable: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.reachable = reachable;
+        if !reachable {
+            state.subscribers.clear();
+        }
+    }
+
+    //lists the node named name as a peer in reach, the same way discovery would
+    pub fn add_peer(&self, name: &str) -> Result<(), TransportError> {
+        let eid = Eid::from_name(name)?;
+        let peer = DtnPeer::new(
+            eid.into(),
+            PeerAddress::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            PeerType::Dynamic,
+            Some(Duration::from_secs(2)),
+            vec![(String::from("mtcp"), Some(16162))],
+            HashMap::new(),
+        );
+        self.state.lock().unwrap().peers.insert(name.to_string(), peer);
+        Ok(())
+    }
+
+    //the node named name went out of reach
+    pub fn remove_peer(&self, name: &str) {
+        self.state.lock().unwrap().peers.remove(name);
+    }
+
+    //whether the node named name is in reach
+    pub fn has_peer(&self, name: &str) -> bool {
+        self.state.lock().unwrap().peers.contains_key(name)
+    }
+
+    //a bundle carrying message arrives from the node named peer on endpoint, a service name
+    //of ours or a whole endpoint id. false if nobody is subscribed to endpoint
+    pub fn receive_from(&self, peer: &str, endpoint: &str, message: &str) -> Result<bool, TransportError> {
+        let mut state = self.state.lock().unwrap();
+        let destination = if endpoint.contains(':') {
+            endpoint.parse()?
+        } else {
+            state.node_id.service(endpoint)?
+        };
+        let source = Eid::from_name(peer)?.service(endpoint.rsplit('/').next().unwrap_or(endpoint))?;
+        let payload = Envelope::new(&write::new_id(), peer, Kind::Text(message.to_string())).to_cbor();
+        let mut bundle = bundle::build(&source, &destination, &source, payload, &SendOptions::default());
+        let bytes = bundle.to_cbor();
+        state.store.insert(bundle.id(), bytes.clone());
+        //subscriptions that ended are dropped on the way
+        let subscribers = state.subscribers.entry(endpoint.to_string()).or_default();
+        subscribers.retain(|subscriber| subscriber.send(bytes.clone()).is_ok());
+        Ok(!subscribers.is_empty())
+    }
+}
+
+impl Transport for MockDaemon {
+    fn node_id(&self) -> Result<Eid, TransportError> {
+        Ok(self.up()?.node_id.clone())
+    }
+
+    fn peers(&self) -> Result<BTreeMap<String, DtnPeer>, TransportError> {
+        Ok(self.up()?.peers.clone())
+    }
+
+    fn insert(&self, bundle: &mut Bundle) -> Result<String, TransportError> {
+        let id = bundle.id();
+        self.up()?.store.insert(id.clone(), bundle.to_cbor());
+        Ok(id)
+    }
+
+    fn bundle_ids(&self) -> Result<Vec<String>, TransportError> {
+        Ok(self.up()?.store.keys().cloned().collect())
+    }
+
+    fn download(&self, bundle_id: &str) -> Result<Vec<u8>, TransportError> {
+        self.up()?.store.get(bundle_id).cloned()
+            .ok_or_else(|| TransportError::Daemon(404, String::from("Bundle not found")))
+    }
+
+    fn register(&self, endpoint: &str) -> Result<(), TransportError> {
+        self.up()?.registered.insert(endpoint.to_string());
+        Ok(())
+    }
+
+    fn unregister(&self, endpoint: &str) -> Result<(), TransportError> {
+        let mut state = self.up()?;
+        state.registered.remove(endpoint);
+        state.subscribers.remove(endpoint);
+        Ok(())
+    }
+
+    fn receive(&self, endpoint: &str, deliver: &dyn Fn(Vec<u8>) -> bool, listening: &dyn Fn() -> bool)
+        -> Result<(), TransportError> {
+        let (sender, bundles) = mpsc::channel();
+        {
+            let mut state = self.up()?;
+            if !state.registered.contains(endpoint) {
+                return Err(TransportError::WebSocket(format!("400 endpoint {} is not registered", endpoint)));
+            }
+            state.subscribers.entry(endpoint.to_string()).or_default().push(sender);
+        }
+        //the sender goes away when the endpoint is unregistered or the daemon is stopped
+        while listening() {
+            match bundles.recv_timeout(LISTENING_CHECK) {
+                Ok(bundle) => {
+                    if !deliver(bundle) {
+                        return Ok(());
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) =>
+                    return Err(TransportError::WebSocket(String::from("mock dtnd closed the subscription"))),
+            }
+        }
+        Ok(())
+    }
+}
+
+//keeps letting peers come into reach and leave and has the ones in reach write to endpoint now and then
+pub fn simulate(daemon: MockDaemon, endpoint: String) {
+    thread::spawn(move || {
+        let mut rng = rand::thread_rng();
+        loop {
+            thread::sleep(SIMULATION_STEP);
+            //now and then the daemon goes away for a step, like a dtnd being restarted
+            if rng.gen_bool(0.05) {
+                daemon.set_reachable(false);
+                thread::sleep(SIMULATION_STEP);
+                daemon.set_reachable(true);
+                continue;
+            }
+            let peer = SIMULATED_PEERS.choose(&mut rng).copied().unwrap_or("alice");
+            if !daemon.has_peer(peer) {
+                let _result = daemon.add_peer(peer);
+            } else if rng.gen_bool(0.2) {
+                daemon.remove_peer(peer);
+            } else {
+                let line = SIMULATED_LINES.choose(&mut rng).copied().unwrap_or("hello");
+                let _result = daemon.receive_from(peer, &endpoint, line);
+            }
+        }
+    });
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+//how urgent a bundle is, carried in a priority block since bpv7 has no field for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Bulk,
+    Normal,
+    Expedited,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Priority::Bulk => write!(f, "bulk"),
+            Priority::Normal => write!(f, "normal"),
+            Priority::Expedited => write!(f, "expedited"),
+        }
+    }
+}
+
+//how a message is sent, set per conversation with /set and per message with /send
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SendOptions {
+    //seconds the bundle may travel before it is dropped
+    pub lifetime: u64,
+    pub priority: Priority,
+    //ask the destination for a status report once the bundle is delivered
+    pub report_delivery: bool,
+    //keep nodes on the way from splitting the bundle into fragments
+    pub no_fragment: bool,
+    //send the bare text instead of an envelope, for peers reading with dtnrecv or other dtn7 apps
+    pub plain: bool,
+    //tell the peer once we read their messages, envelopes only
+    pub receipts: bool,
+}
+
+//the defaults match what dtnd's /send used to do for us
+impl Default for SendOptions {
+    fn default() -> SendOptions {
+        SendOptions {
+            lifetime: 60 * 60,
+            priority: Priority::Normal,
+            report_delivery: true,
+            no_fragment: true,
+            plain: false,
+            receipts: true,
+        }
+    }
+}
+
+impl SendOptions {
+    //applies the leading words that are options, like lifetime=3d priority=bulk report no-fragment plain no-receipts,
+    //and returns the position of the first word that is not one
+    pub fn apply(&mut self, words: &[&str]) -> usize {
+        for (position, word) in words.iter().enumerate() {
+            match word.split_once('=') {
+                Some(("lifetime", value)) => match parse_duration(value) {
+                    Some(lifetime) => self.lifetime = lifetime,
+                    None => return position,
+                },
+                Some(("priority", "bulk")) => self.priority = Priority::Bulk,
+                Some(("priority", "normal")) => self.priority = Priority::Normal,
+                Some(("priority", "expedited")) => self.priority = Priority::Expedited,
+                Some(_) => return position,
+                None => match *word {
+                    "report" => self.report_delivery = true,
+                    "no-report" => self.report_delivery = false,
+                    "fragment" => self.no_fragment = false,
+                    "no-fragment" => self.no_fragment = true,
+                    "plain" => self.plain = true,
+                    "envelope" => self.plain = false,
+                    "receipts" => self.receipts = true,
+                    "no-receipts" => self.receipts = false,
+                    _ => return position,
+                },
+            }
+        }
+        words.len()
+    }
+}
+
+//short form shown next to sent messages, in the same words apply() takes.
+//the envelope and read receipts are left out, they are what nearly every conversation uses
+impl fmt::Display for SendOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "lifetime={} priority={} {} {}",
+               format_duration(self.lifetime),
+               self.priority,
+               if self.report_delivery { "report" } else { "no-report" },
+               if self.no_fragment { "no-fragment" } else { "fragment" })?;
+        if self.plain {
+            write!(f, " plain")?;
+        }
+        if !self.receipts {
+            write!(f, " no-receipts")?;
+        }
+        Ok(())
+    }
+}
+
+//parses durations like 90s, 10m, 6h or 3d into seconds, a plain number counts as seconds
+pub fn parse_duration(duration: &str) -> Option<u64> {
+    let (number, unit) = match duration.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((index, _)) => duration.split_at(index),
+        None => (duration, "s"),
+    };
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    number.parse::<u64>().ok().filter(|number| *number > 0)?.checked_mul(seconds)
+}
+
+//formats seconds in the largest unit parse_duration() reads back exactly
+pub fn format_duration(seconds: u64) -> String {
+    for (unit, length) in [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60)] {
+        if seconds.is_multiple_of(length) {
+            return format!("{}{}", seconds / length, unit);
+        }
+    }
+    format!("{}s", seconds)
+}
@@ -0,0 +1,77 @@
+use std::io;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::config;
+use crate::options::SendOptions;
+use crate::transport::TransportError;
+use crate::write::Message;
+
+//messages the daemon did not take yet, kept across restarts
+const OUTBOX_FILE: &str = "outbox.json";
+//longest wait between two tries of the same bundle
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+//one bundle waiting to be handed to the daemon, channel messages have one per peer
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Pending {
+    //id of the message in chat.json the bundle belongs to
+    pub id: String,
+    //endpoint id the bundle goes to
+    pub target: String,
+    pub message: String,
+    //id of the message it answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    pub options: SendOptions,
+    pub attempts: u32,
+    //unix time in seconds of the next try
+    pub next_try: u64,
+}
+
+impl Pending {
+    pub fn new(message: &Message, target: String, options: &SendOptions) -> Pending {
+        let mut pending = Pending {
+            id: message.id.clone(),
+            target,
+            message: message.message.clone(),
+            reply_to: message.reply_to.clone(),
+            options: options.clone(),
+            attempts: 0,
+            next_try: 0,
+        };
+        pending.failed();
+        pending
+    }
+
+    //whether it is time for the next try
+    pub fn due(&self) -> bool {
+        self.next_try <= config::now()
+    }
+
+    //counts a failed try and schedules the next one, waiting twice as long each time
+    pub fn failed(&mut self) {
+        let backoff = Duration::from_secs(1 << self.attempts.min(16)).min(MAX_BACKOFF);
+        self.attempts += 1;
+        self.next_try = config::now() + backoff.as_secs();
+    }
+}
+
+//whether sending may work later, when the daemon is back or done starting up.
+//anything else, like a bad endpoint id or a bundle the daemon refuses, fails the same way again
+pub fn retryable(err: &TransportError) -> bool {
+    match err {
+        TransportError::Http(_) | TransportError::Io(_) | TransportError::NoNodeId => true,
+        TransportError::Daemon(status, _) => *status >= 500,
+        _ => false,
+    }
+}
+
+//reads outbox.json, there is nothing pending without one
+pub fn load() -> io::Result<Vec<Pending>> {
+    config::read_file(OUTBOX_FILE)
+}
+
+//writes all pending bundles to outbox.json
+pub fn save(outbox: &[Pending]) -> io::Result<()> {
+    config::write_file(OUTBOX_FILE, outbox)
+}
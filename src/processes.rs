@@ -1,8 +1,10 @@
-use std::{process::{Command, Stdio}};
+use std::fs::File;
+use std::io;
+use std::process::{Child, Command, Stdio};
+use crate::config::{self, DaemonConfig};
 
 pub fn hostname() -> String{
-    //same logic as send()
-    //tells the dtn daemon to receive message
+    //tells the system to print its hostname
     let name = Command::new("hostname")
         .output()
         .expect("failed to execute process");
@@ -12,108 +14,63 @@ pub fn hostname() -> String{
     
     stdout.into_owned()
 }
-pub fn dtndkill(id: String){
-    let _kill = Command::new("kill")
-    .arg(&id)
-    .spawn();
-    println!("Killing PID: {}", id);
-}   
 
-pub fn dtndfind() -> Vec<String>{
-    let mut dtnds:Vec<String> = Vec::new();
-    let output = Command::new("sh")
-                      .arg("-c")
-                      .arg("ps -A | grep dtnd")
-                      .output()
-                      .expect("failed to execute ps command");
-
-    // Convert the output into a string slice
-    let output_str = std::str::from_utf8(&output.stdout).unwrap();
-
-    // Split the string into lines and process each line
-    for ps in output_str.lines() {
-        // Split each line into fields and obtain the PID field
-        let fields: Vec<&str> = ps.split_whitespace().collect();
-        let pid = fields[0];
-        dtnds.push(pid.to_string());
-        // Print the PID
-        println!("dtnd process found with PID: {}", pid);
-    }   
-    dtnds
-}
-pub fn send(name: String,  message: String)  -> String{
-    //create new process that runs echo and puts result in echo_output
-    let echo_output = Command::new("echo")
-        .arg(message)
-        .stdout(Stdio::piped())
-        .spawn()
-        .expect("failed to execute process");
-
-    //creates target String dtn://(nodename)/incoming
-    let mut target = String::from("dtn://");
-        target.push_str(name.as_str().trim()); 
-        target.push_str("/incoming");
-
-    //creates new process that tells the dtn daemon to send message
-    let dtnsend_output = Command::new("dtnsend")
-        .arg("-r")
-        .arg(target)
-        .stdin(echo_output.stdout.unwrap())
-        .output()
-        .expect("failed to execute process");
-
-    if dtnsend_output.status.success() {
-        let stdout = String::from_utf8_lossy(&dtnsend_output.stdout);
-        stdout.into_owned()
+//starts our own dtnd with the same arguments as dtndstart.sh, registering all endpoints,
+//its output goes to dtnd.log so it doesnt draw over the tui
+pub fn dtndstart(config: &DaemonConfig, endpoints: &[String]) -> io::Result<Child> {
+    let node_name = if config.node_name.trim().is_empty() {
+        hostname()
     } else {
-        let stderr = String::from_utf8_lossy(&dtnsend_output.stderr);
-        stderr.into_owned()
+        config.node_name.clone()
+    };
+    let log = File::create(config::data_file("dtnd.log"))?;
+    let mut dtnd = Command::new("dtnd");
+    dtnd.arg("-n").arg(node_name.trim());
+    for endpoint in endpoints {
+        dtnd.arg("-e").arg(endpoint);
     }
- 
-}
-
-pub fn receive() -> String{
-    //same logic as send()
-    //tells the dtn daemon to receive message
-    let dtnrecv_output = Command::new("dtnrecv")
-        .arg("-e")
-        .arg("incoming")
-        .arg("-x")
-        .output()
-        .expect("failed to execute process");
-
-    let stdout = String::from_utf8_lossy(&dtnrecv_output.stdout);
-    let _stderr = String::from_utf8_lossy(&dtnrecv_output.stderr);
-    
-    stdout.into_owned()
+    for peer in &config.static_peers {
+        dtnd.arg("-s").arg(peer.url());
+    }
+    dtnd.arg("-C")
+        .arg(&config.cla)
+        .arg("-p")
+        .arg(&config.discovery_period)
+        .arg("-r")
+        .arg(&config.routing)
+        .arg("-D")
+        .arg(&config.db)
+        //a sled store goes next to the client's other files
+        .arg("-W")
+        .arg(config::data_file("dtnd"))
+        .arg("-w")
+        .arg(config.port.to_string())
+        //delivery tracking needs the status reports the bundles ask for
+        .arg("--generate-status-reports")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()
 }
-pub fn peers() -> String{
 
-    //same logic as send()
-    //tells the dtn daemon to get peers list
-    let output = Command::new("dtnquery")
-        .arg("peers")
-        .output()
-        .expect("failed to execute process");
-
-    if output.status.success() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        stdout.into_owned()
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        stderr.into_owned()
+//stops the dtnd started by dtndstart() and waits for it to exit
+pub fn dtndstop(dtnd: &mut Child) -> io::Result<()> {
+    //ask nicely first so dtnd can shut down cleanly, kill it if that fails
+    let terminated = Command::new("kill")
+        .arg(dtnd.id().to_string())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    if !terminated {
+        dtnd.kill()?;
     }
+    dtnd.wait()?;
+    Ok(())
 }
 
-
-// pub fn dtndstart(username: String){
-//     let command = format!("dtnd -n {} -e incoming -C mtcp -r epidemic -p 3s",username.trim());
-//     let _startup = Command::new("gnome-terminal")
-//                       .arg("--")
-//                       .arg("sh")
-//                       .arg("-c")
-//                       .arg(format!("{}", command))
-//                       .output()
-//                       .expect("failed to execute dtnd");
-    
-// }
\ No newline at end of file
+//stops our dtnd and starts it again with the current config, dtnd cant take new static peers while running
+pub fn dtndrestart(dtnd: &mut Child, config: &DaemonConfig, endpoints: &[String]) -> io::Result<()> {
+    dtndstop(dtnd)?;
+    *dtnd = dtndstart(config, endpoints)?;
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use std::io;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::config;
+use crate::decode::DecodeError;
+
+//bundles that could not be read, kept so they can be looked at later
+const QUARANTINE_FILE: &str = "quarantine.json";
+
+//subscriptions of several endpoints may quarantine at the same time
+static QUARANTINE: Mutex<()> = Mutex::new(());
+
+//a bundle that could not be read
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Quarantined {
+    //unix time in seconds it arrived
+    pub received: u64,
+    //endpoint it arrived on
+    pub endpoint: String,
+    pub error: String,
+    //the bundle as the daemon handed it to us, in hex
+    pub bundle: String,
+}
+
+//number of bundles in quarantine.json
+pub fn count() -> io::Result<usize> {
+    let _guard = QUARANTINE.lock().unwrap();
+    let quarantined: Vec<Quarantined> = config::read_file(QUARANTINE_FILE)?;
+    Ok(quarantined.len())
+}
+
+//adds a bundle that arrived on endpoint and could not be read to quarantine.json
+pub fn add(bundle: &[u8], endpoint: &str, error: &DecodeError) -> io::Result<()> {
+    let _guard = QUARANTINE.lock().unwrap();
+    let mut quarantined: Vec<Quarantined> = config::read_file(QUARANTINE_FILE)?;
+    quarantined.push(Quarantined {
+        received: config::now(),
+        endpoint: endpoint.to_string(),
+        error: error.to_string(),
+        bundle: hex::encode(bundle),
+    });
+    config::write_file(QUARANTINE_FILE, &quarantined)
+}
@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::config;
+use crate::options::SendOptions;
+
+//read receipts not sent yet, kept across restarts since the messages are marked read already
+const RECEIPTS_FILE: &str = "receipts.json";
+
+//the read receipts for one peer that go out together
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Batch {
+    //ids of their messages we read
+    pub ids: Vec<String>,
+    //options of the conversation when the last of them was read
+    pub options: SendOptions,
+    //unix time in seconds the batch goes out
+    pub due: u64,
+}
+
+impl Batch {
+    //an empty batch going out after delay
+    pub fn new(options: SendOptions, delay: Duration) -> Batch {
+        Batch { ids: Vec::new(), options, due: config::now() + delay.as_secs() }
+    }
+
+    //whether it is time to send it
+    pub fn due(&self) -> bool {
+        self.due <= config::now()
+    }
+
+    //sends it after delay instead
+    pub fn postpone(&mut self, delay: Duration) {
+        self.due = config::now() + delay.as_secs();
+    }
+}
+
+//reads receipts.json, there are no receipts waiting without one
+pub fn load() -> io::Result<BTreeMap<String, Batch>> {
+    config::read_file(RECEIPTS_FILE)
+}
+
+//writes the batches waiting for each peer to receipts.json
+pub fn save(receipts: &BTreeMap<String, Batch>) -> io::Result<()> {
+    config::write_file(RECEIPTS_FILE, receipts)
+}
@@ -0,0 +1,17 @@
+//chat rooms live on dtn group endpoints, every member registers the same endpoint
+//and sends to it, so a message reaches everyone in the room it is routed to
+
+//group endpoint of a room
+pub fn endpoint(room: &str) -> String {
+    format!("dtn://~{}/chat", room)
+}
+
+//room name of a group endpoint made by endpoint(), None for any other endpoint
+pub fn room_of(endpoint: &str) -> Option<&str> {
+    endpoint.strip_prefix("dtn://~")?.strip_suffix("/chat")
+}
+
+//room names become part of an endpoint id, so only letters, digits, - and _ are allowed
+pub fn valid_name(room: &str) -> bool {
+    !room.is_empty() && room.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
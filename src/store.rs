@@ -0,0 +1,56 @@
+use bp7::dtn_time_now;
+use crate::decode;
+
+//a bundle the daemon holds, as shown in the store browser
+#[derive(Debug, Clone)]
+pub struct StoredBundle {
+    pub id: String,
+    pub source: String,
+    pub destination: String,
+    //size of the whole bundle in bytes
+    pub size: usize,
+    //seconds since the bundle was created, None if its creator had no clock
+    pub age: Option<u64>,
+    //seconds the bundle has left before it expires, negative once it has
+    pub remaining: Option<i64>,
+    pub payload: Vec<u8>,
+}
+
+impl StoredBundle {
+    //reads what the browser shows from a bundle as the daemon hands it out, None if it isnt one
+    pub fn from_cbor(id: String, bytes: &[u8]) -> Option<StoredBundle> {
+        let bundle = decode::decode(bytes).ok()?;
+        //dtn times are milliseconds since 2000, 0 means the creator had no clock
+        let created = bundle.created.dtntime();
+        let age = (created != 0).then(|| dtn_time_now().saturating_sub(created) / 1000);
+        let remaining = age.map(|age| bundle.lifetime.as_secs() as i64 - age as i64);
+        Some(StoredBundle {
+            id,
+            source: bundle.source.to_string(),
+            destination: bundle.destination.to_string(),
+            size: bytes.len(),
+            age,
+            remaining,
+            payload: bundle.payload,
+        })
+    }
+}
+
+//rough duration for the store browser, in the largest unit that fits
+pub fn rough_duration(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+//rough size for the ui, in bytes, KB or MB
+pub fn rough_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} bytes", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
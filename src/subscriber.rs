@@ -0,0 +1,72 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use crate::transport::{Transport, TransportError};
+
+//how long to wait before reconnecting after the daemon went away
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+//handle to a running subscription
+pub struct Subscription {
+    active: Arc<AtomicBool>,
+}
+
+impl Subscription {
+    //stops the subscription, nothing is delivered from now on and
+    //the thread closes its connection to the daemon within LISTENING_CHECK
+    pub fn cancel(&self) {
+        self.active.store(false, Ordering::Relaxed);
+    }
+}
+
+//spawns a thread that keeps a subscription on endpoint open and passes
+//every bundle the daemon pushes to deliver, reconnecting whenever the daemon goes away.
+//the thread ends once deliver returns false, meaning nobody is listening anymore
+pub fn spawn<T, F>(client: T, endpoint: String, deliver: F) -> Subscription
+where
+    T: Transport,
+    F: Fn(Vec<u8>) -> bool + Send + 'static,
+{
+    let active = Arc::new(AtomicBool::new(true));
+    let running = active.clone();
+    thread::spawn(move || {
+        let listening = || running.load(Ordering::Relaxed);
+        let deliver = |bundle| listening() && deliver(bundle);
+        while listening() && subscribe(&client, &endpoint, &deliver, &listening).is_err() {
+            thread::sleep(RECONNECT_DELAY);
+        }
+    });
+    Subscription { active }
+}
+
+//registers and subscribes to endpoint, then streams bundles until the connection breaks or nobody listens
+fn subscribe<T: Transport>(client: &T, endpoint: &str, deliver: &dyn Fn(Vec<u8>) -> bool, listening: &dyn Fn() -> bool)
+    -> Result<(), TransportError> {
+    client.register(endpoint)?;
+    client.receive(endpoint, deliver, listening)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+    use crate::mock::MockDaemon;
+    use crate::transport::LISTENING_CHECK;
+    use super::*;
+
+    #[test]
+    fn cancelled_subscriptions_end_while_nothing_arrives() {
+        let daemon = MockDaemon::new("me").unwrap();
+        let subscription = spawn(daemon.clone(), String::from("~lobby"), |_bundle| true);
+        //the subscription is made on the thread
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !daemon.receive_from("alice", "~lobby", "hello").unwrap() {
+            assert!(Instant::now() < deadline, "the subscription was never made");
+            thread::sleep(Duration::from_millis(50));
+        }
+        subscription.cancel();
+        //the mock drops the subscription once its thread let go of it, so later bundles find nobody subscribed
+        thread::sleep(LISTENING_CHECK * 3);
+        assert!(!daemon.receive_from("alice", "~lobby", "still there?").unwrap());
+    }
+}
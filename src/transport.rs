@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use bp7::eid::EndpointIdError;
+use bp7::Bundle;
+use dtn7::DtnPeer;
+use tungstenite::{Message, WebSocket};
+use crate::eid::Eid;
+
+//how long a single request to the daemon may take before giving up
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+//how long a subscription may stay quiet before the daemon is pinged and its queue is read,
+//a daemon that stays quiet for another such while is taken as gone
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+//how often a quiet subscription checks whether it is still listened to
+pub const LISTENING_CHECK: Duration = Duration::from_secs(1);
+//what dtnd answers /endpoint with when it holds no bundle for it
+const NOTHING_TO_RECEIVE: &[u8] = b"Nothing to receive";
+
+#[derive(Debug)]
+pub enum TransportError {
+    //the daemon could not be reached or the connection broke
+    Http(attohttpc::Error),
+    //the daemon answered, but with an error status and message
+    Daemon(u16, String),
+    //the daemon answered with json we could not understand
+    Json(serde_json::Error),
+    //the daemon answered with an endpoint id we could not parse
+    Endpoint(EndpointIdError),
+    //the socket to the daemon could not be opened
+    Io(io::Error),
+    //the websocket handshake or connection failed
+    WebSocket(String),
+    //bundles need our node id as source, which the daemon did not tell us yet
+    NoNodeId,
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::Http(err) => write!(f, "cannot reach dtnd: {}", err),
+            TransportError::Daemon(status, message) => write!(f, "dtnd answered {}: {}", status, message),
+            TransportError::Json(err) => write!(f, "invalid answer from dtnd: {}", err),
+            TransportError::Endpoint(err) => write!(f, "invalid endpoint id from dtnd: {}", err),
+            TransportError::Io(err) => write!(f, "cannot connect to dtnd: {}", err),
+            TransportError::WebSocket(err) => write!(f, "websocket to dtnd failed: {}", err),
+            TransportError::NoNodeId => write!(f, "dtnd did not tell its node id yet"),
+        }
+    }
+}
+
+impl Error for TransportError {}
+
+impl From<attohttpc::Error> for TransportError {
+    fn from(err: attohttpc::Error) -> Self {
+        TransportError::Http(err)
+    }
+}
+
+impl From<serde_json::Error> for TransportError {
+    fn from(err: serde_json::Error) -> Self {
+        TransportError::Json(err)
+    }
+}
+
+impl From<EndpointIdError> for TransportError {
+    fn from(err: EndpointIdError) -> Self {
+        TransportError::Endpoint(err)
+    }
+}
+
+impl From<io::Error> for TransportError {
+    fn from(err: io::Error) -> Self {
+        TransportError::Io(err)
+    }
+}
+
+impl From<tungstenite::Error> for TransportError {
+    fn from(err: tungstenite::Error) -> Self {
+        TransportError::WebSocket(err.to_string())
+    }
+}
+
+//what the client needs from a daemon, so it can run against dtnd or the in-process mock
+pub trait Transport: Clone + Send + 'static {
+    //endpoint id of the node the daemon runs as
+    fn node_id(&self) -> Result<Eid, TransportError>;
+    //list of peers the daemon currently knows, sorted by node name
+    fn peers(&self) -> Result<BTreeMap<String, DtnPeer>, TransportError>;
+    //hands a bundle we built ourselves to the daemon for forwarding, returns its bundle id
+    fn insert(&self, bundle: &mut Bundle) -> Result<String, TransportError>;
+    //ids of all bundles the daemon holds
+    fn bundle_ids(&self) -> Result<Vec<String>, TransportError>;
+    //the bundle with this id as the daemon holds it, in cbor
+    fn download(&self, bundle_id: &str) -> Result<Vec<u8>, TransportError>;
+    //registers an endpoint with the daemon so bundles for it are kept for us
+    fn register(&self, endpoint: &str) -> Result<(), TransportError>;
+    //removes an endpoint registered with register()
+    fn unregister(&self, endpoint: &str) -> Result<(), TransportError>;
+    //passes every bundle for the registered endpoint to deliver as it arrives, in cbor.
+    //returns Ok once deliver or listening returns false, listening is asked at least every LISTENING_CHECK,
+    //and an error once the connection to the daemon broke
+    fn receive(&self, endpoint: &str, deliver: &dyn Fn(Vec<u8>) -> bool, listening: &dyn Fn() -> bool)
+        -> Result<(), TransportError>;
+}
+
+//client for the REST and websocket interfaces of a dtnd
+#[derive(Clone)]
+pub struct DtndClient {
+    host: String,
+    port: u16,
+}
+
+impl DtndClient {
+    //client for the dtnd whose web interface listens on host:port
+    pub fn with_host_and_port(host: String, port: u16) -> DtndClient {
+        DtndClient { host, port }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://{}:{}{}", self.host, self.port, path)
+    }
+
+    //turns non 2xx answers into a TransportError carrying the daemon's message
+    fn check(response: attohttpc::Response) -> Result<attohttpc::Response, TransportError> {
+        if response.is_success() {
+            Ok(response)
+        } else {
+            let status = response.status().as_u16();
+            let message = response.text().unwrap_or_default();
+            Err(TransportError::Daemon(status, message.trim().to_string()))
+        }
+    }
+
+    //opens a websocket to the daemon's application agent interface,
+    //reads time out after IDLE_TIMEOUT so a daemon gone without closing the connection is noticed
+    fn websocket(&self) -> Result<WebSocket<TcpStream>, TransportError> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+        let url = format!("ws://{}:{}/ws", self.host, self.port);
+        let (socket, _response) = tungstenite::client(url, stream)
+            .map_err(|err| TransportError::WebSocket(err.to_string()))?;
+        Ok(socket)
+    }
+
+    //takes the next bundle the daemon queued for endpoint, in cbor. dtnd queues the bundles that
+    //arrive while no websocket is subscribed or faster than the websocket takes them
+    fn pop(&self, endpoint: &str) -> Result<Option<Vec<u8>>, TransportError> {
+        let response = attohttpc::get(self.url(&format!("/endpoint?{}", endpoint)))
+            .timeout(REQUEST_TIMEOUT)
+            .send()?;
+        let body = DtndClient::check(response)?.bytes()?;
+        Ok((body != NOTHING_TO_RECEIVE).then_some(body))
+    }
+
+    //passes every bundle queued for endpoint to deliver, false once deliver returned false
+    fn drain(&self, endpoint: &str, deliver: &dyn Fn(Vec<u8>) -> bool) -> Result<bool, TransportError> {
+        while let Some(bundle) = self.pop(endpoint)? {
+            if !deliver(bundle) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Transport for DtndClient {
+    fn node_id(&self) -> Result<Eid, TransportError> {
+        let response = attohttpc::get(self.url("/status/nodeid"))
+            .timeout(REQUEST_TIMEOUT)
+            .send()?;
+        let body = DtndClient::check(response)?.text()?;
+        Ok(body.parse()?)
+    }
+
+    fn peers(&self) -> Result<BTreeMap<String, DtnPeer>, TransportError> {
+        let response = attohttpc::get(self.url("/status/peers"))
+            .timeout(REQUEST_TIMEOUT)
+            .send()?;
+        let body = DtndClient::check(response)?.text()?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    fn insert(&self, bundle: &mut Bundle) -> Result<String, TransportError> {
+        let response = attohttpc::post(self.url("/insert"))
+            .timeout(REQUEST_TIMEOUT)
+            .bytes(bundle.to_cbor())
+            .send()?;
+        DtndClient::check(response)?;
+        Ok(bundle.id())
+    }
+
+    fn bundle_ids(&self) -> Result<Vec<String>, TransportError> {
+        let response = attohttpc::get(self.url("/status/bundles"))
+            .timeout(REQUEST_TIMEOUT)
+            .send()?;
+        let body = DtndClient::check(response)?.text()?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    fn download(&self, bundle_id: &str) -> Result<Vec<u8>, TransportError> {
+        let response = attohttpc::get(self.url(&format!("/download?{}", bundle_id)))
+            .timeout(REQUEST_TIMEOUT)
+            .send()?;
+        Ok(DtndClient::check(response)?.bytes()?)
+    }
+
+    fn register(&self, endpoint: &str) -> Result<(), TransportError> {
+        let response = attohttpc::get(self.url(&format!("/register?{}", endpoint)))
+            .timeout(REQUEST_TIMEOUT)
+            .send()?;
+        DtndClient::check(response)?;
+        Ok(())
+    }
+
+    fn unregister(&self, endpoint: &str) -> Result<(), TransportError> {
+        let response = attohttpc::get(self.url(&format!("/unregister?{}", endpoint)))
+            .timeout(REQUEST_TIMEOUT)
+            .send()?;
+        DtndClient::check(response)?;
+        Ok(())
+    }
+
+    fn receive(&self, endpoint: &str, deliver: &dyn Fn(Vec<u8>) -> bool, listening: &dyn Fn() -> bool)
+        -> Result<(), TransportError> {
+        let mut socket = self.websocket()?;
+        //ask for whole bundles instead of just their payload, the decoder needs the sender
+        command(&mut socket, "/bundle")?;
+        //from here on reads give up every LISTENING_CHECK, so a subscription nobody listens to ends while dtnd is quiet
+        socket.get_ref().set_read_timeout(Some(LISTENING_CHECK))?;
+        //bundles may be pushed before the reply to this arrives, so it is checked in the loop below
+        socket.send(Message::text(format!("/subscribe {}", endpoint)))?;
+        //when the last frame arrived or the last ping went out
+        let mut quiet_since = Instant::now();
+        //whether a ping went out after the connection stayed quiet, another quiet while after it ends the connection
+        let mut pinged = false;
+        loop {
+            if !listening() {
+                return Ok(());
+            }
+            let frame = match socket.read() {
+                Err(tungstenite::Error::Io(err))
+                    if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    if quiet_since.elapsed() < IDLE_TIMEOUT {
+                        continue;
+                    }
+                    if pinged {
+                        return Err(TransportError::Io(err));
+                    }
+                    pinged = true;
+                    quiet_since = Instant::now();
+                    socket.send(Message::Ping(Vec::new()))?;
+                    //bundles the websocket could not take in a burst wait in the queue
+                    if !self.drain(endpoint, deliver)? {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                frame => frame?,
+            };
+            quiet_since = Instant::now();
+            pinged = false;
+            let receiving = match frame {
+                Message::Binary(bundle) => deliver(bundle),
+                //bundles that arrived while we were not subscribed are only in the queue
+                Message::Text(reply) if reply.starts_with("200") => self.drain(endpoint, deliver)?,
+                Message::Text(reply) => return Err(TransportError::WebSocket(reply)),
+                //pings are answered by tungstenite on the next read
+                _ => true,
+            };
+            if !receiving {
+                return Ok(());
+            }
+        }
+    }
+}
+
+//sends a text command over the websocket and waits for the daemon to answer it with a 200 status
+fn command(socket: &mut WebSocket<TcpStream>, cmd: &str) -> Result<(), TransportError> {
+    socket.send(Message::text(cmd))?;
+    loop {
+        if let Message::Text(reply) = socket.read()? {
+            if reply.starts_with("200") {
+                return Ok(());
+            }
+            return Err(TransportError::WebSocket(reply));
+        }
+    }
+}
@@ -0,0 +1,594 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use dtn7::DtnPeer;
+use crate::decode::DecodedBundle;
+use crate::eid::Eid;
+use crate::envelope::{Envelope, Format, Kind};
+use crate::options::SendOptions;
+use crate::outbox::{self, Pending};
+use crate::receipts::{self, Batch};
+use crate::store::StoredBundle;
+use crate::subscriber::Subscription;
+use crate::transport::{Transport, TransportError};
+use crate::write::Message;
+use crate::delivery::{self, DeliveryState};
+use crate::{bundle, decode, fragments, quarantine, rooms, subscriber, write, AppEvent};
+
+//how often the daemon is asked for its peers
+const PEER_INTERVAL: Duration = Duration::from_secs(1);
+//most bundles the store browser lists, each one is downloaded
+const MAX_LISTED: usize = 500;
+//how long read receipts for a peer are collected before they go out in one bundle
+const RECEIPT_DELAY: Duration = Duration::from_secs(10);
+
+//requests from the ui to the worker
+pub enum Command {
+    //send a message to the inbox of the peer listed under this name, see Eid::name(),
+    //the sends carry the id of the message they answer, if any
+    Send { peer: String, message: String, reply_to: Option<String>, options: SendOptions },
+    //send a message to the channel endpoint of every peer the daemon currently lists
+    SendChannel { channel: String, message: String, reply_to: Option<String>, options: SendOptions },
+    //send a message to everyone in a room
+    SendRoom { room: String, message: String, reply_to: Option<String>, options: SendOptions },
+    //start listening on the group endpoint of a room
+    JoinRoom(String),
+    //stop listening on the group endpoint of a room
+    LeaveRoom(String),
+    //list the bundles the daemon holds for the store browser
+    ListBundles,
+    //tell the peer listed under this name that we read their messages with these ids
+    Receipt { peer: String, ids: Vec<String>, options: SendOptions },
+    //mark the messages with these ids in the conversation listed under this name read in chat.json,
+    //along with the ones without an id, which can't be told apart
+    MarkRead { conversation: String, ids: Vec<String> },
+}
+
+struct Worker<I, T> {
+    client: T,
+    //service name of our inbox
+    endpoint: String,
+    events: Sender<AppEvent<I>>,
+    node_id: Option<Eid>,
+    //name we go by in the envelopes we send, the node name when empty
+    name: String,
+    //node name of the daemon once known, bundles from it are our own
+    local_node: Arc<Mutex<String>>,
+    //peers of the last poll
+    peers: BTreeMap<String, DtnPeer>,
+    //subscriptions of the rooms we are in, by room name
+    rooms: HashMap<String, Subscription>,
+    //bundles the daemon did not take yet, also in outbox.json
+    outbox: Vec<Pending>,
+    //read receipts waiting to go out, by peer, also in receipts.json
+    receipts: BTreeMap<String, Batch>,
+    //whether the store is being listed, which runs beside the worker since it can take minutes
+    listing: Arc<AtomicBool>,
+}
+
+//starts the worker that owns all daemon I/O and reports back through events,
+//it listens on the inbox endpoint, all channels and all rooms.
+//returns the channel the ui uses to give it commands, or why outbox.json or receipts.json could not be read
+pub fn spawn<I: Send + 'static, T: Transport>(client: T, endpoint: String, name: String, channels: Vec<String>,
+                                               rooms: Vec<String>, events: Sender<AppEvent<I>>) -> io::Result<Sender<Command>> {
+    let (commands, command_rx) = mpsc::channel();
+    let mut worker = Worker {
+        client,
+        endpoint,
+        name,
+        events,
+        node_id: None,
+        local_node: Arc::new(Mutex::new(String::new())),
+        peers: BTreeMap::new(),
+        rooms: HashMap::new(),
+        outbox: outbox::load()?,
+        receipts: receipts::load()?,
+        listing: Arc::new(AtomicBool::new(false)),
+    };
+
+    //the inbox and channels are listened on for as long as the client runs
+    for listen in std::iter::once(worker.endpoint.clone()).chain(channels) {
+        worker.listen(listen);
+    }
+    for room in rooms {
+        worker.join(room);
+    }
+
+    thread::spawn(move || worker.run(command_rx));
+    Ok(commands)
+}
+
+impl<I: Send + 'static, T: Transport> Worker<I, T> {
+    //polls the daemon every PEER_INTERVAL and handles commands in between,
+    //stops once the ui hung up on either channel
+    fn run(mut self, commands: Receiver<Command>) {
+        loop {
+            if !self.poll() {
+                return;
+            }
+            let next_poll = Instant::now() + PEER_INTERVAL;
+            loop {
+                let timeout = next_poll.saturating_duration_since(Instant::now());
+                match commands.recv_timeout(timeout) {
+                    Ok(command) => {
+                        if !self.handle(command) {
+                            return;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        }
+    }
+
+    //asks the daemon for its node id and peers, false once the ui is gone
+    fn poll(&mut self) -> bool {
+        //ask for the node id until the daemon tells us, it may still be starting up
+        if self.node_id.is_none() {
+            self.node_id = self.client.node_id().ok();
+            if let Some(node_id) = &self.node_id {
+                *self.local_node.lock().unwrap() = node_id.name();
+                if self.events.send(AppEvent::NodeId(node_id.clone())).is_err() {
+                    return false;
+                }
+            }
+        }
+        //an unreachable daemon reads as no neighbours
+        self.peers = self.client.peers().unwrap_or_default();
+        self.events.send(AppEvent::PeerUpdate(self.peers.clone())).is_ok() && self.retry() && {
+            self.send_receipts();
+            true
+        }
+    }
+
+    //carries out a command of the ui, false once the ui is gone
+    fn handle(&mut self, command: Command) -> bool {
+        let (conversation, message, targets) = match command {
+            Command::Send { peer, message, reply_to, options } => {
+                let target = Eid::from_name(&peer).and_then(|node| node.service(&self.endpoint));
+                let message = Message { endpoint: self.endpoint.clone(), message, reply_to, options: Some(options), ..Default::default() };
+                (peer, message, vec![target])
+            }
+            Command::SendChannel { channel, message, reply_to, options } => {
+                //every peer gets its own copy
+                let targets = self.peers.values().map(|peer| Eid::from(peer.eid.clone()).service(&channel)).collect();
+                let message = Message { endpoint: channel.clone(), message, reply_to, options: Some(options), ..Default::default() };
+                (format!("#{}", channel), message, targets)
+            }
+            Command::SendRoom { room, message, reply_to, options } => {
+                let endpoint = rooms::endpoint(&room);
+                let target = endpoint.parse::<Eid>();
+                (format!("~{}", room), Message { endpoint, message, reply_to, options: Some(options), ..Default::default() }, vec![target])
+            }
+            Command::JoinRoom(room) => {
+                self.join(room);
+                return true;
+            }
+            Command::LeaveRoom(room) => {
+                if let Some(subscription) = self.rooms.remove(&room) {
+                    subscription.cancel();
+                    let _result = self.client.unregister(&rooms::endpoint(&room));
+                }
+                return true;
+            }
+            Command::ListBundles => {
+                self.list_bundles();
+                return true;
+            }
+            Command::Receipt { peer, ids, options } => {
+                let batch = self.receipts.entry(peer).or_insert_with(|| Batch::new(options.clone(), RECEIPT_DELAY));
+                batch.ids.extend(ids);
+                batch.options = options;
+                let _result = receipts::save(&self.receipts);
+                return true;
+            }
+            Command::MarkRead { conversation, ids } => {
+                let shown = |message: &Message| {
+                    !message.read && !message.sender.ends_with("(self)")
+                        && (message.id.is_empty() || ids.contains(&message.id))
+                        && message.conversation(&self.endpoint) == conversation
+                };
+                let _result = write::update_messages(shown, |message| message.read = true);
+                return true;
+            }
+        };
+        //create a special name of sender when sender is myself, to update my chat.json
+        let mut message = Message { id: write::new_id(), sender: conversation + "(self)", ..message };
+        let options = message.options.clone().unwrap_or_default();
+        //copies the daemon cant take right now go to the outbox, the first failure is reported
+        let mut error = None;
+        for target in targets {
+            let result = target.map_err(TransportError::from).and_then(|target| {
+                let kind = Kind::Text(message.message.clone());
+                let result = self.send(&target, &message.id, kind, message.reply_to.as_deref(), &options);
+                if result.as_ref().is_err_and(outbox::retryable) {
+                    self.outbox.push(Pending::new(&message, target.to_string(), &options));
+                }
+                result
+            });
+            match result {
+                Ok(bundle_id) => message.bundle_ids.push(bundle_id),
+                Err(err) => { error.get_or_insert(err); }
+            }
+        }
+        //a channel without peers in reach sends nothing, which is not a failure
+        message.delivery = if !message.bundle_ids.is_empty() {
+            Some(DeliveryState::Queued)
+        } else if self.outbox.iter().any(|pending| pending.id == message.id) {
+            let _result = outbox::save(&self.outbox);
+            Some(DeliveryState::Pending)
+        } else {
+            error.as_ref().map(|_| DeliveryState::Failed)
+        };
+        let _result = write::log_message(&message);
+        self.events.send(AppEvent::SendResult(message, error)).is_ok()
+    }
+
+    //downloads the bundles the daemon holds on a thread of its own, so sending and polling go on meanwhile,
+    //and reports them to the ui. asking again while a listing runs is left to that one
+    fn list_bundles(&self) {
+        if self.listing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let client = self.client.clone();
+        let events = self.events.clone();
+        let listing = self.listing.clone();
+        thread::spawn(move || {
+            let stored = stored_bundles(&client);
+            listing.store(false, Ordering::SeqCst);
+            let _result = events.send(AppEvent::Store(stored));
+        });
+    }
+
+    //tries the bundles in the outbox that are due again, false once the ui is gone
+    fn retry(&mut self) -> bool {
+        if !self.outbox.iter().any(Pending::due) {
+            return true;
+        }
+        let mut outbox = std::mem::take(&mut self.outbox);
+        let mut outcomes = Vec::new();
+        outbox.retain_mut(|pending| {
+            if !pending.due() {
+                return true;
+            }
+            let result = pending.target.parse::<Eid>()
+                .map_err(TransportError::from)
+                .and_then(|target| {
+                    let kind = Kind::Text(pending.message.clone());
+                    self.send(&target, &pending.id, kind, pending.reply_to.as_deref(), &pending.options)
+                });
+            match result {
+                Err(err) if outbox::retryable(&err) => {
+                    pending.failed();
+                    true
+                }
+                result => {
+                    outcomes.push((pending.id.clone(), result));
+                    false
+                }
+            }
+        });
+        self.outbox = outbox;
+        let _result = outbox::save(&self.outbox);
+        for (id, result) in outcomes {
+            let still_pending = self.outbox.iter().any(|pending| pending.id == id);
+            let mut updated = None;
+            let _result = write::update_messages(|message| message.id == id, |message| {
+                match &result {
+                    Ok(bundle_id) => {
+                        message.bundle_ids.push(bundle_id.clone());
+                        message.advance(DeliveryState::Queued);
+                    }
+                    //the last copy failed for good and none got out
+                    Err(_) if !still_pending && message.delivery == Some(DeliveryState::Pending) => {
+                        message.delivery = Some(DeliveryState::Failed);
+                    }
+                    Err(_) => {}
+                }
+                updated = Some(message.clone());
+            });
+            if let Some(message) = updated {
+                if self.events.send(AppEvent::Retried(message, result.err())).is_err() {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    //sends the read receipts that waited long enough, one bundle per peer.
+    //they are tried again later while the daemon is out of reach and dropped on any other error
+    fn send_receipts(&mut self) {
+        let due: Vec<String> = self.receipts.iter()
+            .filter(|(_peer, batch)| batch.due())
+            .map(|(peer, _batch)| peer.clone())
+            .collect();
+        if due.is_empty() {
+            return;
+        }
+        for peer in due {
+            let Some(mut batch) = self.receipts.remove(&peer) else { continue };
+            let result = Eid::from_name(&peer)
+                .and_then(|node| node.service(&self.endpoint))
+                .map_err(TransportError::from)
+                .and_then(|target| {
+                    let kind = Kind::Read { ids: batch.ids.clone() };
+                    self.send(&target, &write::new_id(), kind, None, &batch.options)
+                });
+            if result.as_ref().is_err_and(outbox::retryable) {
+                batch.postpone(RECEIPT_DELAY);
+                self.receipts.insert(peer, batch);
+            }
+        }
+        let _result = receipts::save(&self.receipts);
+    }
+
+    //sends what kind holds to the target endpoint in a bundle of our own, returns its bundle id.
+    //plain text has no room for the id of the message it answers, it goes out without it
+    fn send(&self, target: &Eid, id: &str, kind: Kind, reply_to: Option<&str>, options: &SendOptions)
+            -> Result<String, TransportError> {
+        let source = self.node_id.as_ref().ok_or(TransportError::NoNodeId)?;
+        //status reports come back to our inbox, where listen() picks them out
+        let report_to = source.service(&self.endpoint)?;
+        //others see us under our own name, or our node name if we didnt pick one
+        let sender = if self.name.is_empty() { source.name() } else { self.name.clone() };
+        let payload = match kind {
+            //the trailing newline keeps the text on its own line in dtnrecv's output
+            Kind::Text(message) if options.plain => format!("{}\n", message).into_bytes(),
+            kind => Envelope { reply_to: reply_to.map(str::to_string), ..Envelope::new(id, &sender, kind) }.to_cbor(),
+        };
+        self.client.insert(&mut bundle::build(source, target, &report_to, payload, options))
+    }
+
+    //listens on the group endpoint of room
+    fn join(&mut self, room: String) {
+        if !self.rooms.contains_key(&room) {
+            let subscription = self.listen(rooms::endpoint(&room));
+            self.rooms.insert(room, subscription);
+        }
+    }
+
+    //subscribes to endpoint, every message arriving on it is logged in chat.json and passed to the ui.
+    //the daemon also hands us what we sent to a group endpoint ourselves, those are dropped
+    fn listen(&self, endpoint: String) -> Subscription {
+        let events = self.events.clone();
+        let local_node = self.local_node.clone();
+        let service = endpoint.clone();
+        subscriber::spawn(self.client.clone(), endpoint, move |bytes| {
+            //bundles that can't be read are kept out of the chat log and put in quarantine
+            let bundle = match decode::decode(&bytes) {
+                Ok(bundle) => bundle,
+                Err(err) => {
+                    let _result = quarantine::add(&bytes, &service, &err);
+                    return events.send(AppEvent::Quarantined(service.clone(), err)).is_ok();
+                }
+            };
+            //a corrupted status report could claim anything, it goes to quarantine instead
+            if let Some(err) = bundle.integrity.error().filter(|_| bundle.administrative_record) {
+                let _result = quarantine::add(&bytes, &service, &err);
+                return events.send(AppEvent::Quarantined(service.clone(), err)).is_ok();
+            }
+            //fragments wait on disk until the whole payload is there
+            let bundle = if bundle.fragment.is_some() {
+                if let Err(err) = fragments::check(&bundle) {
+                    let _result = quarantine::add(&bytes, &service, &err);
+                    return events.send(AppEvent::Quarantined(service.clone(), err)).is_ok();
+                }
+                let (progress, complete) = match fragments::add(bundle, &service) {
+                    Ok(reassembly) => reassembly,
+                    Err(_) => return true,
+                };
+                if events.send(AppEvent::Fragment(progress)).is_err() {
+                    return false;
+                }
+                match complete {
+                    Some(bundle) => bundle,
+                    None => return true,
+                }
+            } else {
+                bundle
+            };
+            //status reports about our own bundles arrive like messages
+            if let Some((bundle_id, state)) = delivery::status_report(&bundle) {
+                let _result = write::update_messages(|message| message.sent_in(&bundle_id), |message| message.advance(state));
+                return events.send(AppEvent::Delivery(bundle_id, state)).is_ok();
+            }
+            if bundle.sender() == *local_node.lock().unwrap() {
+                return true;
+            }
+            let envelope = Envelope::read(&bundle.payload);
+            //acks and read receipts tell how far our messages got, they dont show up themselves
+            let receipt = match &envelope {
+                Some(Envelope { kind: Kind::Ack { ids }, .. }) => Some((ids.clone(), DeliveryState::Delivered)),
+                Some(Envelope { kind: Kind::Read { ids }, .. }) => Some((ids.clone(), DeliveryState::Read)),
+                _ => None,
+            };
+            if let Some((ids, state)) = receipt {
+                let acked = |message: &Message| message.sender.ends_with("(self)") && ids.contains(&message.id);
+                let _result = write::update_messages(acked, |message| message.advance(state));
+                return events.send(AppEvent::Receipt(ids, state)).is_ok();
+            }
+            let message = decode_bundle(&bundle, &service, envelope);
+            //updates chat log chat.json
+            let _result = write::log_message(&message);
+            events.send(AppEvent::IncomingMessage(message)).is_ok()
+        })
+    }
+}
+
+//the chat message in a bundle pushed by the daemon on endpoint, taken from its envelope if it has one.
+//other tools send bare text, anything else is described
+fn decode_bundle(bundle: &DecodedBundle, endpoint: &str, envelope: Option<Envelope>) -> Message {
+    let mut metadata = bundle.metadata();
+    let (id, message, reply_to) = match envelope {
+        Some(envelope) => {
+            metadata.display_name = Some(envelope.sender.clone());
+            metadata.sent = Some(envelope.sent);
+            metadata.format = Some(Format::Envelope);
+            (envelope.id.clone(), envelope.describe(), envelope.reply_to)
+        }
+        None => {
+            metadata.format = Some(if bundle.text().is_ok() { Format::Text } else { Format::Binary });
+            (String::new(), bundle.message(), None)
+        }
+    };
+    Message {
+        id,
+        sender: bundle.sender(),
+        message,
+        reply_to,
+        metadata: Some(metadata),
+        endpoint: endpoint.to_string(),
+        ..Default::default()
+    }
+}
+
+//the bundles the daemon holds, bundles gone by the time they are asked for are left out
+fn stored_bundles<T: Transport>(client: &T) -> Result<Vec<StoredBundle>, TransportError> {
+    let ids = client.bundle_ids()?;
+    Ok(ids.into_iter()
+        .take(MAX_LISTED)
+        .filter_map(|id| {
+            let bytes = client.download(&id).ok()?;
+            StoredBundle::from_cbor(id, &bytes)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::mock::MockDaemon;
+    use super::*;
+
+    //longest a test waits for the worker to report something
+    const PATIENCE: Duration = Duration::from_secs(10);
+
+    //a worker for the node me on a mock daemon, listening on incoming
+    fn start() -> (MockDaemon, Sender<Command>, Receiver<AppEvent<()>>) {
+        let daemon = MockDaemon::new("me").unwrap();
+        let (events, event_rx) = mpsc::channel();
+        let commands = spawn(daemon.clone(), String::from("incoming"), String::new(), Vec::new(), Vec::new(), events).unwrap();
+        (daemon, commands, event_rx)
+    }
+
+    //the first thing wanted picks out of the events that arrive within PATIENCE
+    fn wait_for<R>(events: &Receiver<AppEvent<()>>, wanted: impl Fn(AppEvent<()>) -> Option<R>) -> R {
+        let deadline = Instant::now() + PATIENCE;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let event = events.recv_timeout(timeout).expect("the worker did not report in time");
+            if let Some(found) = wanted(event) {
+                return found;
+            }
+        }
+    }
+
+    //names of the peers in a peer update
+    fn peer_names(event: AppEvent<()>) -> Option<Vec<String>> {
+        match event {
+            AppEvent::PeerUpdate(peers) => Some(peers.values().map(|peer| Eid::from(peer.eid.clone()).name()).collect()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn peers_come_and_go() {
+        let _guard = config::test_data_dir("peers");
+        let (daemon, _commands, events) = start();
+        daemon.add_peer("alice").unwrap();
+        wait_for(&events, |event| peer_names(event).filter(|names| names == &["alice"]));
+        daemon.remove_peer("alice");
+        wait_for(&events, |event| peer_names(event).filter(|names| names.is_empty()));
+    }
+
+    #[test]
+    fn received_messages_reach_the_ui_and_the_log() {
+        let _guard = config::test_data_dir("receive");
+        let (daemon, _commands, events) = start();
+        //the subscription to incoming is made on a thread of its own
+        let deadline = Instant::now() + PATIENCE;
+        while !daemon.receive_from("alice", "incoming", "hello").unwrap() {
+            assert!(Instant::now() < deadline, "the worker never subscribed to incoming");
+            thread::sleep(Duration::from_millis(50));
+        }
+        let message = wait_for(&events, |event| match event {
+            AppEvent::IncomingMessage(message) => Some(message),
+            _ => None,
+        });
+        assert_eq!(message.message, "hello");
+        assert_eq!(message.sender, "alice");
+        assert_eq!(message.endpoint, "incoming");
+        let logged = write::read_messages().unwrap();
+        assert!(logged.iter().any(|logged| logged.id == message.id && logged.message == "hello"));
+    }
+
+    #[test]
+    fn outbox_is_retried_once_the_daemon_is_back() {
+        let (_guard, dir) = config::test_data_dir("outbox");
+        let (daemon, commands, events) = start();
+        wait_for(&events, |event| matches!(event, AppEvent::NodeId(_)).then_some(()));
+        daemon.set_reachable(false);
+        let message = String::from("are you there?");
+        commands.send(Command::Send { peer: String::from("alice"), message, reply_to: None, options: SendOptions::default() }).unwrap();
+        let (sent, error) = wait_for(&events, |event| match event {
+            AppEvent::SendResult(message, error) => Some((message, error)),
+            _ => None,
+        });
+        assert_eq!(sent.delivery, Some(DeliveryState::Pending));
+        assert!(error.is_some());
+        assert!(dir.join("outbox.json").exists());
+        daemon.set_reachable(true);
+        let (retried, error) = wait_for(&events, |event| match event {
+            AppEvent::Retried(message, error) => Some((message, error)),
+            _ => None,
+        });
+        assert!(error.is_none());
+        assert_eq!(retried.id, sent.id);
+        assert_eq!(retried.delivery, Some(DeliveryState::Queued));
+        assert_eq!(retried.bundle_ids.len(), 1);
+        assert!(outbox::load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn read_receipts_wait_on_disk() {
+        let _guard = config::test_data_dir("receipts");
+        let (_daemon, commands, events) = start();
+        let ids = vec![String::from("first"), String::from("second")];
+        commands.send(Command::Receipt { peer: String::from("alice"), ids: ids.clone(), options: SendOptions::default() }).unwrap();
+        //commands are handled in order, the list of the store comes after the receipt is saved
+        commands.send(Command::ListBundles).unwrap();
+        wait_for(&events, |event| matches!(event, AppEvent::Store(_)).then_some(()));
+        let waiting = receipts::load().unwrap();
+        assert_eq!(waiting.get("alice").map(|batch| &batch.ids), Some(&ids));
+        assert!(!waiting["alice"].due());
+    }
+
+    #[test]
+    fn shown_messages_are_marked_read_in_the_log() {
+        let _guard = config::test_data_dir("mark-read");
+        let (_daemon, commands, events) = start();
+        let message = |id: &str, sender: &str| Message {
+            id: id.to_string(),
+            sender: sender.to_string(),
+            message: String::from("hi"),
+            endpoint: String::from("incoming"),
+            ..Default::default()
+        };
+        //shown, arrived after the conversation was shown, plain text and in another conversation
+        for logged in [message("shown", "alice"), message("later", "alice"), message("", "alice"), message("other", "bob")] {
+            write::log_message(&logged).unwrap();
+        }
+        commands.send(Command::MarkRead { conversation: String::from("alice"), ids: vec![String::from("shown")] }).unwrap();
+        //commands are handled in order, the list of the store comes after the log is updated
+        commands.send(Command::ListBundles).unwrap();
+        wait_for(&events, |event| matches!(event, AppEvent::Store(_)).then_some(()));
+        let read: Vec<bool> = write::read_messages().unwrap().iter().map(|logged| logged.read).collect();
+        assert_eq!(read, [true, false, true, false]);
+    }
+}
@@ -1,40 +1,149 @@
 use std::fs::File;
-use std::io::{self, Read, Write};
+use std::io::{self, Write};
+use std::sync::Mutex;
 use serde_json::Value;
 use serde::{Deserialize, Serialize};
+use crate::config;
+use crate::decode::Metadata;
+use crate::delivery::DeliveryState;
+use crate::options::SendOptions;
+use crate::rooms;
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Message {
-    sender: String,
-    message: String,
+//the chat log, in the data directory
+const CHAT_FILE: &str = "chat.json";
+
+//chat.json is written from the worker and the subscriber thread, only one at a time
+static CHAT_LOG: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Message {
+    //random id of the message from its envelope, empty in logs from before the outbox
+    //and for messages from tools that send plain text
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    pub sender: String,
+    pub message: String,
+    //id of the message this one answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    //service name the message came in on or was sent to, empty in logs from before channels
+    #[serde(default)]
+    pub endpoint: String,
+    //lifetime, priority and flags a message of ours was sent with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<SendOptions>,
+    //ids of the bundles a message of ours went out in, one per peer for channels
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bundle_ids: Vec<String>,
+    //how far a message of ours got, the furthest of its bundles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<DeliveryState>,
+    //when a received message was created and how it travelled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+    //whether a received message was shown in its open conversation
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read: bool,
+}
+
+//creates chat.json with an empty log if it doesnt exist yet
+pub fn create_log() -> io::Result<()> {
+    let path = config::data_file(CHAT_FILE);
+    if !path.exists() {
+        //initializes chat.json with an empty array
+        let mut file = File::create(path)?;
+        file.write_all(b"[]\n")?;
+    }
+    Ok(())
+}
+
+//appends message to chat.json, entries this version can't read are kept as they are
+pub fn log_message(message: &Message) -> io::Result<()> {
+    let _guard = CHAT_LOG.lock().unwrap();
+    let mut messages: Vec<Value> = config::read_file(CHAT_FILE)?;
+    messages.push(serde_json::to_value(message)?);
+    config::write_file(CHAT_FILE, &messages)
 }
 
-pub fn write_message(the_sender: &str,the_message: &str) -> io::Result<()> {
+//reads the whole chat log
+pub fn read_messages() -> io::Result<Vec<Message>> {
+    let _guard = CHAT_LOG.lock().unwrap();
+    config::read_file(CHAT_FILE)
+}
 
-    //read the contents of the file into contents
-    let mut file = File::open("chat.json")?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+impl Message {
+    //moves the delivery state on, see DeliveryState::advance()
+    pub fn advance(&mut self, state: DeliveryState) {
+        self.delivery = Some(self.delivery.map_or(state, |delivery| delivery.advance(state)));
+    }
 
-    //deserializes contents into a serde_json Value
-    let mut messages: Value = serde_json::from_str(&contents).unwrap_or(Value::Array(vec![]));
+    //whether the message went out in the bundle bundle_id
+    pub fn sent_in(&self, bundle_id: &str) -> bool {
+        self.bundle_ids.iter().any(|id| id == bundle_id)
+    }
 
-    //Create the new message
-    let message = Message {
-        sender: the_sender.to_owned(),
-        message: the_message.to_owned(),
-    };
-    //pass the message into to_value function to get Value object
-    let message_json = serde_json::to_value(message).unwrap();
-    //push into messages
-    messages.as_array_mut().unwrap().push(message_json);
+    //name of the entry in the peer list the message belongs to when our inbox is the service inbox,
+    //the peer for messages on our inbox, ~(room) for room messages and #(endpoint) for channel messages
+    pub fn conversation(&self, inbox: &str) -> String {
+        if self.endpoint.is_empty() || self.endpoint == inbox {
+            self.sender.trim_end_matches("(self)").to_string()
+        } else if let Some(room) = rooms::room_of(&self.endpoint) {
+            format!("~{}", room)
+        } else {
+            format!("#{}", self.endpoint)
+        }
+    }
+}
 
-    //serialize the updated messages to json
-    let new_contents = serde_json::to_string_pretty(&messages)?;
+//random version 4 uuid for a new message
+pub fn new_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
 
-    //write the updated json back to chat.json
-    let mut file = File::create("chat.json")?;
-    file.write_all(new_contents.as_bytes())?;
+//applies change to every message in chat.json that matches
+pub fn update_messages<P, F>(matches: P, mut change: F) -> io::Result<()>
+where
+    P: Fn(&Message) -> bool,
+    F: FnMut(&mut Message),
+{
+    let _guard = CHAT_LOG.lock().unwrap();
+    let mut messages: Vec<Message> = config::read_file(CHAT_FILE)?;
+    messages.iter_mut().filter(|message| matches(message)).for_each(&mut change);
+    config::write_file(CHAT_FILE, &messages)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::config;
+    use super::*;
+
+    #[test]
+    fn unreadable_log_is_left_alone() {
+        let (_guard, dir) = config::test_data_dir("unreadable-log");
+        let path = dir.join(CHAT_FILE);
+        //cut off in the middle of the second entry, as an interrupted write would leave it
+        let broken = r#"[{"sender":"alice","message":"hi"},{"sender":"bo"#;
+        fs::write(&path, broken).unwrap();
+        let err = update_messages(|_message| true, |message| message.read = true).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(log_message(&Message::default()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), broken);
+    }
+
+    #[test]
+    fn updates_replace_the_log() {
+        let (_guard, dir) = config::test_data_dir("update-log");
+        let message = Message { id: new_id(), sender: String::from("alice"), message: String::from("hi"), ..Default::default() };
+        log_message(&message).unwrap();
+        update_messages(|logged| logged.id == message.id, |logged| logged.read = true).unwrap();
+        let logged = read_messages().unwrap();
+        assert_eq!(logged.len(), 1);
+        assert!(logged[0].read);
+        assert!(!dir.join("chat.json.tmp").exists());
+    }
 }